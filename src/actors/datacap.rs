@@ -0,0 +1,150 @@
+use super::{decode_fields, decode_value, field, id_key, load_fields};
+use crate::bigint::BigInt;
+use crate::{
+    hamt, ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+    TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+
+/// ID of the DataCap token actor.
+pub const DATACAP_TOKEN_ACTOR_ID: u64 = 7;
+
+/// Position of the token state in the DataCap actor state.
+const TOKEN_FIELD: usize = 1;
+/// Position of the balances HAMT in the token state.
+const BALANCES_FIELD: usize = 1;
+/// Position of the bit width of the token's HAMTs in the token state.
+const BIT_WIDTH_FIELD: usize = 3;
+
+/// Proof of a client's DataCap balance in the DataCap token actor.
+///
+/// The proof chains the DataCap actor's state in the state tree to a segment from the actor's
+/// state through the token's balances HAMT, keyed by the client's actor ID.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct DataCapBalanceProof {
+    client: Address,
+    actor: ActorStateProof,
+    balance: Proof,
+}
+
+impl DataCapBalanceProof {
+    /// Verifies the proof against the state root and returns the client's balance, in the
+    /// token's atto units. Clients without a balance have a balance of zero.
+    pub fn verify(&self, state_root: &Cid) -> Result<TokenAmount> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the client's balance.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<TokenAmount> {
+        let datacap = self.actor.verify_with(state_root, hasher)?;
+        if self.actor.address().id() != Some(DATACAP_TOKEN_ACTOR_ID) {
+            return Err(Error::InvalidNode("actor is not the DataCap actor".into()).into());
+        }
+
+        let mut nodes = self.balance.nodes().iter().rev();
+        let (root, bit_width) = balances(&decode_fields(nodes.next(), &datacap.head, hasher)?)?;
+        match hamt::verify(nodes, &root, &id_key(&self.client)?, bit_width, hasher)? {
+            Some(value) => Ok(decode_value::<BigInt>(&value)?.0),
+            None => Ok(0),
+        }
+    }
+
+    /// Returns the address of the client.
+    pub fn client(&self) -> &Address {
+        &self.client
+    }
+}
+
+/// Returns the root and bit width of the balances HAMT from the DataCap actor state.
+fn balances(fields: &[Ipld]) -> Result<(Cid, u32)> {
+    let token: Vec<Ipld> = field(fields, TOKEN_FIELD)?;
+    let bit_width = field(&token, BIT_WIDTH_FIELD)?;
+    if bit_width == 0 || bit_width > 8 {
+        return Err(Error::InvalidNode(format!("invalid HAMT bit width {}", bit_width)).into());
+    }
+    Ok((field(&token, BALANCES_FIELD)?, bit_width))
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the DataCap balance of the client, which must be an ID address.
+    pub fn generate_datacap_balance_proof(
+        &self,
+        state_root: &Cid,
+        client: &Address,
+    ) -> Result<DataCapBalanceProof> {
+        let addr = Address::new_id(DATACAP_TOKEN_ACTOR_ID);
+        let datacap = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+        let (root, bit_width) = balances(&load_fields(self, &datacap.head)?)?;
+        let (_, leaf) = hamt::lookup(self, &root, &id_key(client)?, bit_width)?;
+        Ok(DataCapBalanceProof {
+            client: client.clone(),
+            actor: self.generate_actor_proof(state_root, &addr)?,
+            balance: self.generate_proof_raw(leaf, Some(&datacap.head))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor, u64_key};
+    use crate::DEFAULT_HASH_CODE;
+    use forest_ipld::to_ipld;
+
+    #[test]
+    fn datacap_balance_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let balances: Vec<_> = (1000..1200)
+            .map(|id| (u64_key(id), to_ipld(BigInt((id as i128) << 70)).unwrap()))
+            .collect();
+        let balances = hamt::build(&bs, &balances, 3);
+        let allowances = hamt::build(&bs, &[], 3);
+        let token = Ipld::List(vec![
+            to_ipld(BigInt(1 << 90)).unwrap(),
+            Ipld::Link(balances),
+            Ipld::Link(allowances),
+            Ipld::Integer(3),
+        ]);
+        let state = Ipld::List(vec![Ipld::Bytes(Address::new_id(6).to_bytes()), token]);
+        let head = bs.put(&state, DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(
+            &bs,
+            &[(DATACAP_TOKEN_ACTOR_ID, test_actor(allowances, head))],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        for id in &[1000, 1077, 1199] {
+            let client = Address::new_id(*id);
+            let proof = p_gen
+                .generate_datacap_balance_proof(&state_root, &client)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap(), (*id as i128) << 70);
+        }
+
+        let proof = p_gen
+            .generate_datacap_balance_proof(&state_root, &Address::new_id(5))
+            .unwrap();
+        assert_eq!(proof.verify(&state_root).unwrap(), 0);
+
+        // Balances are keyed by actor ID.
+        let robust = Address::from_bytes(&[2; 21]).unwrap();
+        assert!(p_gen
+            .generate_datacap_balance_proof(&state_root, &robust)
+            .is_err());
+    }
+}
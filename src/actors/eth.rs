@@ -0,0 +1,389 @@
+use super::evm::{decode_nonce, decode_word, slot_key, BYTECODE_HASH_FIELD, CONTRACT_STATE_FIELD};
+use super::init::{ADDRESS_MAP_FIELD, INIT_ACTOR_ID};
+use super::{decode_fields, field, load_fields};
+use crate::kamt::{self, EVM_BIT_WIDTH};
+use crate::state::decode_state_root;
+use crate::{
+    hamt, load_bytes, ActorState, Address, Blake2bHasher, Error, ProofGenerator, ProofHasher,
+};
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use forest_encoding::serde_bytes::ByteBuf;
+use ipld_blockstore::BlockStore;
+use serde::{Deserialize, Serialize};
+
+/// Prefix of an Ethereum address which masks an actor ID, followed by the big-endian ID.
+const MASKED_ID_PREFIX: [u8; 12] = [0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+/// Namespace of delegated addresses managed by the Ethereum address manager actor.
+const EAM_NAMESPACE: u8 = 10;
+
+/// Proof of an EVM contract's account and storage, in the JSON shape of an `eth_getProof`
+/// response so that it can be consumed by Ethereum tooling.
+///
+/// Nodes are hex encoded and ordered from the state root, like Ethereum proofs. The account
+/// proof is the path through the actors HAMT to the contract, ending with the contract's EVM
+/// state, and for addresses which do not mask an ID it is preceded by the resolution of the
+/// address through the init actor. The storage hash is the Cid of the contract storage KAMT, and
+/// each storage proof is the path through the KAMT to the slot.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EthAccountProof {
+    /// Ethereum address of the contract.
+    pub address: String,
+    /// Nodes proving the contract's actor and EVM state, ordered from the state root.
+    pub account_proof: Vec<String>,
+    /// Balance of the contract, in attoFIL.
+    pub balance: String,
+    /// Keccak-256 hash of the contract's bytecode.
+    pub code_hash: String,
+    /// Nonce of the contract.
+    pub nonce: String,
+    /// Bytes of the Cid of the contract storage KAMT.
+    pub storage_hash: String,
+    /// Proofs of the requested storage slots.
+    pub storage_proof: Vec<EthStorageProof>,
+}
+
+/// Proof of a storage slot within an [EthAccountProof].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EthStorageProof {
+    /// Storage slot, as 32 bytes.
+    pub key: String,
+    /// Value of the slot, which is zero if the slot is unset.
+    pub value: String,
+    /// Nodes of the contract storage KAMT, ordered from its root.
+    pub proof: Vec<String>,
+}
+
+impl EthAccountProof {
+    /// Verifies the response against the state root, checking that the account fields and the
+    /// values of all storage slots match the proven state.
+    pub fn verify(&self, state_root: &Cid) -> Result<()> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the response against the state root, computing the [Cid] of each node with the
+    /// hasher.
+    pub fn verify_with<H: ProofHasher + ?Sized>(&self, state_root: &Cid, hasher: &H) -> Result<()> {
+        let address = decode_hex(&self.address)?;
+        if address.len() != 20 {
+            return Err(invalid("address must be 20 bytes"));
+        }
+        let nodes = decode_nodes(&self.account_proof)?;
+        let mut nodes = nodes.iter();
+
+        let id = match masked_id(&address) {
+            Some(id) => id,
+            None => {
+                let init = verify_actor(&mut nodes, state_root, INIT_ACTOR_ID, hasher)?;
+                let map: Cid = field(
+                    &decode_fields(nodes.next(), &init.head, hasher)?,
+                    ADDRESS_MAP_FIELD,
+                )?;
+                let value = hamt::verify_path(
+                    &mut nodes,
+                    &map,
+                    &delegated(&address)?.to_bytes(),
+                    hamt::DEFAULT_BIT_WIDTH,
+                    hasher,
+                )?
+                .ok_or(Error::KeyNotFound)?;
+                forest_ipld::from_ipld(&value).map_err(|e| anyhow!(e))?
+            }
+        };
+        let actor = verify_actor(&mut nodes, state_root, id, hasher)?;
+        let fields = decode_fields(nodes.next(), &actor.head, hasher)?;
+        if nodes.next().is_some() {
+            return Err(Error::UnexpectedNodes.into());
+        }
+
+        check_quantity(
+            &self.balance,
+            &(actor.balance as u128).to_be_bytes(),
+            "balance",
+        )?;
+        let nonce = decode_nonce(&fields)?;
+        check_quantity(&self.nonce, &nonce.to_be_bytes(), "nonce")?;
+        let code_hash: ByteBuf = field(&fields, BYTECODE_HASH_FIELD)?;
+        if decode_hex(&self.code_hash)? != code_hash.as_slice() {
+            return Err(invalid("code hash does not match"));
+        }
+        let root: Cid = field(&fields, CONTRACT_STATE_FIELD)?;
+        if decode_hex(&self.storage_hash)? != root.to_bytes() {
+            return Err(invalid("storage hash does not match"));
+        }
+
+        for storage in &self.storage_proof {
+            let key = decode_hex(&storage.key)?;
+            if key.len() != 32 {
+                return Err(invalid("storage key must be 32 bytes"));
+            }
+            let mut slot = [0u8; 32];
+            slot.copy_from_slice(&key);
+            let nodes = decode_nodes(&storage.proof)?;
+            let value = kamt::verify(&nodes, &root, &slot, slot_key(&slot), EVM_BIT_WIDTH, hasher)?
+                .as_ref()
+                .map(decode_word)
+                .transpose()?
+                .unwrap_or_default();
+            check_quantity(&storage.value, &value, "storage value")?;
+        }
+        Ok(())
+    }
+}
+
+/// Verifies the path from the state root to the actor, consuming only the nodes of the path.
+fn verify_actor<'a, I, H>(
+    nodes: &mut I,
+    state_root: &Cid,
+    id: u64,
+    hasher: &H,
+) -> Result<ActorState>
+where
+    I: Iterator<Item = &'a Vec<u8>>,
+    H: ProofHasher + ?Sized,
+{
+    let actors = decode_state_root(nodes.next(), state_root, hasher)?;
+    let key = Address::new_id(id).to_bytes();
+    let value = hamt::verify_path(nodes, &actors, &key, hamt::DEFAULT_BIT_WIDTH, hasher)?
+        .ok_or(Error::KeyNotFound)?;
+    forest_ipld::from_ipld(&value).map_err(|e| anyhow!(e))
+}
+
+/// Returns the actor ID masked by the Ethereum address, if it is a masked ID address.
+fn masked_id(address: &[u8]) -> Option<u64> {
+    if address[..12] != MASKED_ID_PREFIX {
+        return None;
+    }
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&address[12..]);
+    Some(u64::from_be_bytes(id))
+}
+
+/// Returns the delegated address of the Ethereum address.
+fn delegated(address: &[u8]) -> Result<Address> {
+    let mut bz = vec![4, EAM_NAMESPACE];
+    bz.extend_from_slice(address);
+    Address::from_bytes(&bz)
+}
+
+fn invalid(msg: &str) -> anyhow::Error {
+    Error::InvalidEthProof(msg.into()).into()
+}
+
+/// Encodes the bytes as `0x` prefixed hex data.
+fn encode_hex(bz: &[u8]) -> String {
+    format!("0x{}", hex::encode(bz))
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    let s = s
+        .strip_prefix("0x")
+        .ok_or_else(|| invalid("hex data must be 0x prefixed"))?;
+    hex::decode(s).map_err(|e| invalid(&e.to_string()))
+}
+
+fn decode_nodes(nodes: &[String]) -> Result<Vec<Vec<u8>>> {
+    nodes.iter().map(|n| decode_hex(n)).collect()
+}
+
+/// Encodes the big-endian integer as a hex quantity, which has no leading zeros.
+fn encode_quantity(bz: &[u8]) -> String {
+    let digits = hex::encode(bz);
+    match digits.trim_start_matches('0') {
+        "" => "0x0".into(),
+        digits => format!("0x{}", digits),
+    }
+}
+
+/// Checks that the hex quantity is the big-endian integer.
+fn check_quantity(s: &str, expected: &[u8], name: &str) -> Result<()> {
+    let digits = s
+        .strip_prefix("0x")
+        .ok_or_else(|| invalid("quantity must be 0x prefixed"))?;
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) {
+        return Err(invalid("quantity must not have leading zeros"));
+    }
+    if !digits.eq_ignore_ascii_case(&encode_quantity(expected)[2..]) {
+        return Err(invalid(&format!("{} does not match", name)));
+    }
+    Ok(())
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates an `eth_getProof` response for the EVM contract at the Ethereum address, with
+    /// proofs of the storage slots. Slots do not have to be set.
+    pub fn generate_eth_proof(
+        &self,
+        state_root: &Cid,
+        address: &[u8; 20],
+        slots: &[[u8; 32]],
+    ) -> Result<EthAccountProof> {
+        let mut account_proof = Vec::new();
+        let id = match masked_id(address) {
+            Some(id) => id,
+            None => {
+                let resolution =
+                    self.generate_address_resolution_proof(state_root, &delegated(address)?)?;
+                let id = resolution.verify(state_root)?.ok_or(Error::KeyNotFound)?;
+                let nodes = resolution.actor_proof().proof().nodes().iter().rev();
+                let nodes = nodes.chain(resolution.address_map_proof().nodes().iter().rev());
+                account_proof.extend(nodes.map(|n| encode_hex(n)));
+                id
+            }
+        };
+
+        let addr = Address::new_id(id);
+        let actor = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+        let fields = load_fields(self, &actor.head)?;
+        let root: Cid = field(&fields, CONTRACT_STATE_FIELD)?;
+        let actor_proof = self.generate_actor_proof(state_root, &addr)?;
+        account_proof.extend(
+            actor_proof
+                .proof()
+                .nodes()
+                .iter()
+                .rev()
+                .map(|n| encode_hex(n)),
+        );
+        account_proof.push(encode_hex(&load_bytes(self, &actor.head)?));
+
+        let mut storage_proof = Vec::new();
+        for slot in slots {
+            let (value, leaf) = kamt::lookup(self, &root, slot, slot_key(slot), EVM_BIT_WIDTH)?;
+            let value = value
+                .as_ref()
+                .map(decode_word)
+                .transpose()?
+                .unwrap_or_default();
+            let proof = self.generate_proof_raw(leaf, Some(&root))?;
+            storage_proof.push(EthStorageProof {
+                key: encode_hex(slot),
+                value: encode_quantity(&value),
+                proof: proof.nodes().iter().rev().map(|n| encode_hex(n)).collect(),
+            });
+        }
+
+        let nonce = decode_nonce(&fields)?;
+        let code_hash: ByteBuf = field(&fields, BYTECODE_HASH_FIELD)?;
+        Ok(EthAccountProof {
+            address: encode_hex(address),
+            account_proof,
+            balance: encode_quantity(&(actor.balance as u128).to_be_bytes()),
+            code_hash: encode_hex(&code_hash),
+            nonce: encode_quantity(&nonce.to_be_bytes()),
+            storage_hash: encode_hex(&root.to_bytes()),
+            storage_proof,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::evm::build_evm_state;
+    use crate::actors::{build_actors, test_actor};
+    use crate::DEFAULT_HASH_CODE;
+    use forest_ipld::Ipld;
+
+    fn word(last: u8) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[31] = last;
+        word
+    }
+
+    #[test]
+    fn eth_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let eth_address = [0x42; 20];
+        let slots: Vec<_> = (0..30).map(|i| (word(i), word(i + 1))).collect();
+        let head = build_evm_state(&bs, &slots, true);
+        let address_map = hamt::build(
+            &bs,
+            &[(
+                delegated(&eth_address).unwrap().to_bytes(),
+                Ipld::Integer(1500),
+            )],
+            hamt::DEFAULT_BIT_WIDTH,
+        );
+        let init_state = Ipld::List(vec![
+            Ipld::Link(address_map),
+            Ipld::Integer(1501),
+            Ipld::String("testnet".into()),
+        ]);
+        let init_head = bs.put(&init_state, DEFAULT_HASH_CODE).unwrap();
+        let code = bs.put(&"evm", DEFAULT_HASH_CODE).unwrap();
+        let legacy_head = build_evm_state(&bs, &slots, false);
+        let state_root = build_actors(
+            &bs,
+            &[
+                (INIT_ACTOR_ID, test_actor(code, init_head)),
+                (
+                    1500,
+                    ActorState {
+                        balance: 1 << 64,
+                        ..test_actor(code, head)
+                    },
+                ),
+                (1600, test_actor(code, legacy_head)),
+            ],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        let mut masked = [0u8; 20];
+        masked[..12].copy_from_slice(&MASKED_ID_PREFIX);
+        masked[12..].copy_from_slice(&1500u64.to_be_bytes());
+        let direct = p_gen
+            .generate_eth_proof(&state_root, &masked, &[word(3), word(200)])
+            .unwrap();
+        direct.verify(&state_root).unwrap();
+        assert_eq!(direct.balance, "0x10000000000000000");
+        assert_eq!(direct.nonce, "0x1");
+        assert_eq!(direct.code_hash, encode_hex(&[0xab; 32]));
+        assert_eq!(direct.storage_proof[0].value, "0x4");
+        assert_eq!(direct.storage_proof[1].value, "0x0");
+
+        // Contracts from before transient data was added have the nonce at a different field.
+        masked[12..].copy_from_slice(&1600u64.to_be_bytes());
+        let legacy = p_gen
+            .generate_eth_proof(&state_root, &masked, &[word(3)])
+            .unwrap();
+        legacy.verify(&state_root).unwrap();
+        assert_eq!(legacy.nonce, "0x1");
+
+        // Delegated addresses are resolved through the init actor.
+        let resolved = p_gen
+            .generate_eth_proof(&state_root, &eth_address, &[word(3)])
+            .unwrap();
+        resolved.verify(&state_root).unwrap();
+        assert!(resolved.account_proof.len() > direct.account_proof.len());
+
+        // Response round trips through JSON.
+        let json = serde_json::to_string(&resolved).unwrap();
+        assert!(json.contains("\"accountProof\""));
+        assert!(json.contains("\"storageHash\""));
+        let parsed: EthAccountProof = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, resolved);
+        parsed.verify(&state_root).unwrap();
+
+        // Claimed values must match the proven state.
+        let mut forged = resolved.clone();
+        forged.storage_proof[0].value = "0x5".into();
+        assert!(forged.verify(&state_root).is_err());
+        let mut forged = resolved.clone();
+        forged.balance = "0x1".into();
+        assert!(forged.verify(&state_root).is_err());
+        let mut forged = resolved.clone();
+        forged.nonce = "0x01".into();
+        assert!(forged.verify(&state_root).is_err());
+        let mut forged = resolved;
+        forged.address = encode_hex(&[0x43; 20]);
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
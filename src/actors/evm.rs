@@ -0,0 +1,261 @@
+use super::{decode_fields, decode_value, field, load_fields};
+use crate::kamt::{self, EVM_BIT_WIDTH};
+use crate::{ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::{serde_bytes, tuple::*};
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use serde::{de, Deserialize, Deserializer, Serializer};
+
+/// Position of the Keccak-256 hash of the contract's bytecode in the EVM actor state.
+pub(super) const BYTECODE_HASH_FIELD: usize = 1;
+/// Position of the contract storage KAMT in the EVM actor state.
+pub(super) const CONTRACT_STATE_FIELD: usize = 2;
+/// Number of fields of the EVM actor state before transient data was added by FIP-0097.
+const LEGACY_FIELD_COUNT: usize = 5;
+/// Number of fields of the EVM actor state with transient data.
+const FIELD_COUNT: usize = 6;
+/// Position of the contract's nonce in the EVM actor state before transient data was added.
+const LEGACY_NONCE_FIELD: usize = 3;
+/// Position of the contract's nonce in the EVM actor state, which follows the transient data.
+const NONCE_FIELD: usize = 4;
+
+/// Proof of the value of a storage slot of an EVM contract, which proves the value loaded by
+/// `SLOAD` like the storage proofs of `eth_getProof`.
+///
+/// The proof chains the contract's actor state in the state tree to a segment from the EVM
+/// actor's state through the contract storage KAMT, which may prove that the slot is unset.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct StorageSlotProof {
+    #[serde(with = "slot_bytes")]
+    slot: [u8; 32],
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl StorageSlotProof {
+    /// Verifies the proof against the state root and returns the big-endian value of the slot,
+    /// or `None` if the slot is unset, in which case `SLOAD` returns zero.
+    pub fn verify(&self, state_root: &Cid) -> Result<Option<[u8; 32]>> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the big-endian value of the slot, or `None` if the slot is unset.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Option<[u8; 32]>> {
+        let contract = self.actor.verify_with(state_root, hasher)?;
+        let mut nodes = self.path.nodes().iter().rev();
+        let root: Cid = field(
+            &decode_fields(nodes.next(), &contract.head, hasher)?,
+            CONTRACT_STATE_FIELD,
+        )?;
+        kamt::verify(
+            nodes,
+            &root,
+            &self.slot,
+            slot_key(&self.slot),
+            EVM_BIT_WIDTH,
+            hasher,
+        )?
+        .as_ref()
+        .map(decode_word)
+        .transpose()
+    }
+
+    /// Returns the address of the contract.
+    pub fn contract(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns the big-endian storage slot being proven.
+    pub fn slot(&self) -> &[u8; 32] {
+        &self.slot
+    }
+
+    /// Returns reference to the segment proving the contract's actor state.
+    pub fn actor_proof(&self) -> &ActorStateProof {
+        &self.actor
+    }
+
+    /// Returns reference to the segment proving the slot from the contract's state.
+    pub fn storage_proof(&self) -> &Proof {
+        &self.path
+    }
+}
+
+/// Returns the key a slot is stored as in the KAMT, which is the big-endian slot without leading
+/// zeros. The full 32 bytes are used as the key's hash.
+pub(super) fn slot_key(slot: &[u8; 32]) -> &[u8] {
+    let start = slot.iter().position(|b| *b != 0).unwrap_or(32);
+    &slot[start..]
+}
+
+/// Decodes the contract's nonce from the fields of the EVM actor state, which is located by the
+/// number of fields as the state may be from before transient data was added.
+pub(super) fn decode_nonce(fields: &[Ipld]) -> Result<u64> {
+    match fields.len() {
+        LEGACY_FIELD_COUNT => field(fields, LEGACY_NONCE_FIELD),
+        FIELD_COUNT => field(fields, NONCE_FIELD),
+        n => Err(Error::InvalidNode(format!("EVM actor state has {} fields", n)).into()),
+    }
+}
+
+/// Decodes a storage value, which is a big-endian word stored without leading zeros.
+pub(super) fn decode_word(value: &Ipld) -> Result<[u8; 32]> {
+    let bz: serde_bytes::ByteBuf = decode_value(value)?;
+    if bz.len() > 32 {
+        return Err(Error::InvalidNode("storage value exceeds 32 bytes".into()).into());
+    }
+    let mut word = [0u8; 32];
+    word[32 - bz.len()..].copy_from_slice(&bz);
+    Ok(word)
+}
+
+mod slot_bytes {
+    use super::*;
+
+    pub fn serialize<S>(slot: &[u8; 32], serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(slot)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> std::result::Result<[u8; 32], D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bz = serde_bytes::ByteBuf::deserialize(deserializer)?;
+        let mut slot = [0u8; 32];
+        if bz.len() != 32 {
+            return Err(de::Error::invalid_length(bz.len(), &"32 bytes"));
+        }
+        slot.copy_from_slice(&bz);
+        Ok(slot)
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the value of the storage slot of the EVM contract. The slot does
+    /// not have to be set.
+    pub fn generate_storage_slot_proof(
+        &self,
+        state_root: &Cid,
+        contract: &Address,
+        slot: &[u8; 32],
+    ) -> Result<StorageSlotProof> {
+        let actor = self
+            .get_actor(state_root, contract)?
+            .ok_or(Error::KeyNotFound)?;
+        let root = field(&load_fields(self, &actor.head)?, CONTRACT_STATE_FIELD)?;
+        let (_, leaf) = kamt::lookup(self, &root, slot, slot_key(slot), EVM_BIT_WIDTH)?;
+        Ok(StorageSlotProof {
+            slot: *slot,
+            actor: self.generate_actor_proof(state_root, contract)?,
+            path: self.generate_proof_raw(leaf, Some(&actor.head))?,
+        })
+    }
+}
+
+/// Builds an EVM actor state with the storage slots, returning the head.
+#[cfg(test)]
+pub(crate) fn build_evm_state<BS: BlockStore>(
+    bs: &BS,
+    slots: &[([u8; 32], [u8; 32])],
+    transient_data: bool,
+) -> Cid {
+    use crate::DEFAULT_HASH_CODE;
+
+    let entries: Vec<_> = slots
+        .iter()
+        .map(|(slot, value)| {
+            let value = Ipld::Bytes(slot_key(value).to_vec());
+            (slot.to_vec(), slot_key(slot).to_vec(), value)
+        })
+        .collect();
+    let storage = kamt::build(bs, &entries, EVM_BIT_WIDTH);
+    let bytecode = bs.put(&"bytecode", DEFAULT_HASH_CODE).unwrap();
+    let mut state = vec![
+        Ipld::Link(bytecode),
+        Ipld::Bytes(vec![0xab; 32]),
+        Ipld::Link(storage),
+        Ipld::Integer(1),
+        Ipld::Null,
+    ];
+    if transient_data {
+        // Transient storage root and lifespan of the origin actor and nonce.
+        let transient = bs.put(&"transient", DEFAULT_HASH_CODE).unwrap();
+        let lifespan = Ipld::List(vec![Ipld::Integer(1000), Ipld::Integer(9)]);
+        state.insert(3, Ipld::List(vec![Ipld::Link(transient), lifespan]));
+    }
+    let state = Ipld::List(state);
+    bs.put(&state, DEFAULT_HASH_CODE).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::DEFAULT_HASH_CODE;
+
+    fn word(prefix: u8, last: u8) -> [u8; 32] {
+        let mut word = [0u8; 32];
+        word[0] = prefix;
+        word[31] = last;
+        word
+    }
+
+    #[test]
+    fn storage_slot_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        // Sequential slots share long prefixes, hashed slots are spread out.
+        let slots: Vec<_> = (0..20)
+            .map(|i| (word(0, i), word(0, i + 100)))
+            .chain((1..20).map(|i| (word(i * 13, i), word(i, 0))))
+            .collect();
+        let head = build_evm_state(&bs, &slots, true);
+        let contract = Address::new_id(1500);
+        let code = cid::new_from_cbor(&[0x80], DEFAULT_HASH_CODE);
+        let state_root = build_actors(&bs, &[(1500, test_actor(code, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        for (slot, value) in &slots {
+            let proof = p_gen
+                .generate_storage_slot_proof(&state_root, &contract, slot)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap(), Some(*value));
+        }
+
+        let proof = p_gen
+            .generate_storage_slot_proof(&state_root, &contract, &word(0, 1))
+            .unwrap();
+        assert_eq!(proof.contract(), &contract);
+
+        // Unset slots are proven absent.
+        for slot in &[word(0, 50), word(0xff, 0), word(13, 2)] {
+            let proof = p_gen
+                .generate_storage_slot_proof(&state_root, &contract, slot)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap(), None);
+        }
+
+        // Slot changed to one set in a different node is rejected.
+        let proof = p_gen
+            .generate_storage_slot_proof(&state_root, &contract, &word(0, 3))
+            .unwrap();
+        let forged = StorageSlotProof {
+            slot: word(13, 1),
+            ..proof
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
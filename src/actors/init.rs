@@ -0,0 +1,245 @@
+use super::{decode_value, verify_hamt_field};
+use crate::{
+    ActorState, ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+    Protocol,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use ipld_blockstore::BlockStore;
+
+/// ID of the init actor.
+pub const INIT_ACTOR_ID: u64 = 1;
+
+/// Position of the address map HAMT in the init actor state.
+pub(super) const ADDRESS_MAP_FIELD: usize = 0;
+
+/// Proof of the resolution of a robust or delegated address to its ID through the init actor.
+///
+/// The proof chains the init actor's state in the state tree to a segment through its
+/// `address_map` HAMT, which may prove that the address is not assigned an ID.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AddressResolutionProof {
+    address: Address,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl AddressResolutionProof {
+    /// Verifies the proof against the state root and returns the ID of the address, or `None`
+    /// if the address has not been assigned an ID.
+    pub fn verify(&self, state_root: &Cid) -> Result<Option<u64>> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the ID of the address, or `None` if the address has not been assigned an ID.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Option<u64>> {
+        let init = self.actor.verify_with(state_root, hasher)?;
+        if self.actor.address().id() != Some(INIT_ACTOR_ID) {
+            return Err(Error::InvalidNode("actor is not the init actor".into()).into());
+        }
+        let value = verify_hamt_field(
+            &self.path,
+            &init.head,
+            ADDRESS_MAP_FIELD,
+            &self.address.to_bytes(),
+            hasher,
+        )?;
+        value.as_ref().map(decode_value).transpose()
+    }
+
+    /// Returns the address being resolved.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns reference to the segment proving the init actor's state.
+    pub fn actor_proof(&self) -> &ActorStateProof {
+        &self.actor
+    }
+
+    /// Returns reference to the segment proving the address from the init actor's state.
+    pub fn address_map_proof(&self) -> &Proof {
+        &self.path
+    }
+}
+
+/// Proof of the state of an actor by its robust or delegated address. This chains the
+/// resolution of the address to its ID with the proof of the actor's state by its ID.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ResolvedActorProof {
+    resolution: AddressResolutionProof,
+    actor: ActorStateProof,
+}
+
+impl ResolvedActorProof {
+    /// Verifies the proof against the state root and returns the ID and state of the actor.
+    pub fn verify(&self, state_root: &Cid) -> Result<(u64, ActorState)> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the ID and state of the actor.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<(u64, ActorState)> {
+        let id = self
+            .resolution
+            .verify_with(state_root, hasher)?
+            .ok_or(Error::KeyNotFound)?;
+        if self.actor.address() != &Address::new_id(id) {
+            return Err(Error::InvalidNode("actor does not match resolved ID".into()).into());
+        }
+        Ok((id, self.actor.verify_with(state_root, hasher)?))
+    }
+
+    /// Returns the address being resolved.
+    pub fn address(&self) -> &Address {
+        self.resolution.address()
+    }
+
+    /// Returns reference to the segment resolving the address.
+    pub fn resolution_proof(&self) -> &AddressResolutionProof {
+        &self.resolution
+    }
+
+    /// Returns reference to the segment proving the actor's state.
+    pub fn actor_proof(&self) -> &ActorStateProof {
+        &self.actor
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the resolution of the address to its ID through the init actor.
+    /// The address does not have to be assigned an ID, but must not be an ID address.
+    pub fn generate_address_resolution_proof(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+    ) -> Result<AddressResolutionProof> {
+        if addr.protocol() == Protocol::Id {
+            return Err(Error::InvalidAddress("ID addresses do not need to be resolved").into());
+        }
+        let init_addr = Address::new_id(INIT_ACTOR_ID);
+        let init = self
+            .get_actor(state_root, &init_addr)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(AddressResolutionProof {
+            address: addr.clone(),
+            actor: self.generate_actor_proof(state_root, &init_addr)?,
+            path: self.generate_hamt_field_proof(
+                &init.head,
+                ADDRESS_MAP_FIELD,
+                &addr.to_bytes(),
+            )?,
+        })
+    }
+
+    /// Generates a proof of the state of the actor with the robust or delegated address.
+    pub fn generate_resolved_actor_proof(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+    ) -> Result<ResolvedActorProof> {
+        let resolution = self.generate_address_resolution_proof(state_root, addr)?;
+        let id = resolution.verify(state_root)?.ok_or(Error::KeyNotFound)?;
+        Ok(ResolvedActorProof {
+            resolution,
+            actor: self.generate_actor_proof(state_root, &Address::new_id(id))?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::{hamt, DEFAULT_HASH_CODE};
+    use forest_ipld::Ipld;
+
+    fn robust(i: u8) -> Address {
+        let mut bz = vec![1];
+        bz.extend_from_slice(&[i; 20]);
+        Address::from_bytes(&bz).unwrap()
+    }
+
+    #[test]
+    fn address_resolution_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let mut delegated = vec![4, 10];
+        delegated.extend_from_slice(&[0xaa; 20]);
+        let delegated = Address::from_bytes(&delegated).unwrap();
+        let mut entries: Vec<_> = (0..50u8)
+            .map(|i| (robust(i).to_bytes(), Ipld::Integer(1000 + i as i128)))
+            .collect();
+        entries.push((delegated.to_bytes(), Ipld::Integer(2000)));
+        let address_map = hamt::build(&bs, &entries, hamt::DEFAULT_BIT_WIDTH);
+        let state = Ipld::List(vec![
+            Ipld::Link(address_map),
+            Ipld::Integer(2001),
+            Ipld::String("testnet".into()),
+        ]);
+        let head = bs.put(&state, DEFAULT_HASH_CODE).unwrap();
+        let code = bs.put(&"account", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(
+            &bs,
+            &[
+                (INIT_ACTOR_ID, test_actor(code, head)),
+                (1007, test_actor(code, code)),
+                (2000, test_actor(code, code)),
+            ],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen
+            .generate_address_resolution_proof(&state_root, &robust(7))
+            .unwrap();
+        assert_eq!(proof.verify(&state_root).unwrap(), Some(1007));
+
+        let proof = p_gen
+            .generate_resolved_actor_proof(&state_root, &robust(7))
+            .unwrap();
+        assert_eq!(
+            proof.verify(&state_root).unwrap(),
+            (1007, test_actor(code, code))
+        );
+        let proof = p_gen
+            .generate_resolved_actor_proof(&state_root, &delegated)
+            .unwrap();
+        assert_eq!(proof.verify(&state_root).unwrap().0, 2000);
+
+        // Addresses which are not assigned an ID are proven absent.
+        let proof = p_gen
+            .generate_address_resolution_proof(&state_root, &robust(100))
+            .unwrap();
+        assert_eq!(proof.verify(&state_root).unwrap(), None);
+        assert!(p_gen
+            .generate_resolved_actor_proof(&state_root, &robust(100))
+            .is_err());
+        assert!(p_gen
+            .generate_address_resolution_proof(&state_root, &Address::new_id(1007))
+            .is_err());
+
+        // Actor segment must be for the resolved ID.
+        let forged = ResolvedActorProof {
+            resolution: p_gen
+                .generate_address_resolution_proof(&state_root, &delegated)
+                .unwrap(),
+            actor: p_gen
+                .generate_actor_proof(&state_root, &Address::new_id(1007))
+                .unwrap(),
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
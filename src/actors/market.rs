@@ -0,0 +1,419 @@
+use super::{decode_value, verify_amt_field};
+use crate::{
+    bigint, ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+    TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
+
+/// ID of the storage market actor.
+pub const STORAGE_MARKET_ACTOR_ID: u64 = 5;
+
+/// Position of the deal proposals AMT in the market actor state.
+const PROPOSALS_FIELD: usize = 0;
+/// Position of the deal states AMT in the market actor state.
+const STATES_FIELD: usize = 1;
+
+/// Label of a deal proposal, which can be either a string or bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Label {
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl Serialize for Label {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Label::String(s) => serializer.serialize_str(s),
+            Label::Bytes(b) => serializer.serialize_bytes(b),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Label {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct LabelVisitor;
+        impl<'de> de::Visitor<'de> for LabelVisitor {
+            type Value = Label;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("string or bytes")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<Label, E> {
+                Ok(Label::String(v.to_owned()))
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> std::result::Result<Label, E> {
+                Ok(Label::Bytes(v.to_vec()))
+            }
+        }
+        // Labels are untagged, so the type of the encoded value decides the variant.
+        deserializer.deserialize_any(LabelVisitor)
+    }
+}
+
+/// Proposal of a storage deal between a client and a provider.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct DealProposal {
+    pub piece_cid: Cid,
+    pub piece_size: u64,
+    pub verified_deal: bool,
+    pub client: Address,
+    pub provider: Address,
+    pub label: Label,
+    pub start_epoch: i64,
+    pub end_epoch: i64,
+    #[serde(with = "bigint")]
+    pub storage_price_per_epoch: TokenAmount,
+    #[serde(with = "bigint")]
+    pub provider_collateral: TokenAmount,
+    #[serde(with = "bigint")]
+    pub client_collateral: TokenAmount,
+}
+
+/// Version of the market actor, which determines the layout of its [DealState].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarketVersion {
+    /// Market actor before v9, with deal states of only the epochs.
+    V8,
+    /// Market actor v9 to v12, with deal states which include the verified claim.
+    V9,
+    /// Market actor from v13, with deal states which include the sector number.
+    V13,
+}
+
+/// State of an activated storage deal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DealState {
+    /// Sector the deal is stored in, only included from market v13.
+    pub sector_number: Option<u64>,
+    pub sector_start_epoch: i64,
+    pub last_updated_epoch: i64,
+    /// Epoch the deal was slashed at, or -1 if it has not been slashed.
+    pub slash_epoch: i64,
+    /// ID of the verified claim of the deal, or zero if it has none. Only included in market
+    /// v9 to v12.
+    pub verified_claim: Option<u64>,
+}
+
+impl DealState {
+    /// Decodes the deal state with the layout of the market version.
+    fn decode(value: &Ipld, version: MarketVersion) -> Result<Self> {
+        Ok(match version {
+            MarketVersion::V8 => {
+                let (start, updated, slash): (i64, i64, i64) = decode_value(value)?;
+                DealState {
+                    sector_number: None,
+                    sector_start_epoch: start,
+                    last_updated_epoch: updated,
+                    slash_epoch: slash,
+                    verified_claim: None,
+                }
+            }
+            MarketVersion::V9 => {
+                let (start, updated, slash, claim): (i64, i64, i64, u64) = decode_value(value)?;
+                DealState {
+                    sector_number: None,
+                    sector_start_epoch: start,
+                    last_updated_epoch: updated,
+                    slash_epoch: slash,
+                    verified_claim: Some(claim),
+                }
+            }
+            MarketVersion::V13 => {
+                let (sector, start, updated, slash): (u64, i64, i64, i64) = decode_value(value)?;
+                DealState {
+                    sector_number: Some(sector),
+                    sector_start_epoch: start,
+                    last_updated_epoch: updated,
+                    slash_epoch: slash,
+                    verified_claim: None,
+                }
+            }
+        })
+    }
+}
+
+/// Encodes the deal state with the layout of the market version which has its fields.
+impl Serialize for DealState {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let epochs = (
+            self.sector_start_epoch,
+            self.last_updated_epoch,
+            self.slash_epoch,
+        );
+        match (self.sector_number, self.verified_claim) {
+            (Some(sector), None) => (sector, epochs.0, epochs.1, epochs.2).serialize(serializer),
+            (None, Some(claim)) => (epochs.0, epochs.1, epochs.2, claim).serialize(serializer),
+            (None, None) => epochs.serialize(serializer),
+            (Some(_), Some(_)) => Err(ser::Error::custom(
+                "deal state has both a sector number and a verified claim",
+            )),
+        }
+    }
+}
+
+/// Storage deal, returned from verifying a [DealProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deal {
+    pub proposal: DealProposal,
+    /// State of the deal, or `None` if the deal has not been activated.
+    pub state: Option<DealState>,
+}
+
+/// Proof of a storage deal's proposal and state in the market actor.
+///
+/// The proof chains the market actor's state in the state tree to a segment through the
+/// proposals AMT and a segment through the states AMT, which may prove that the deal has no
+/// state yet.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct DealProof {
+    deal_id: u64,
+    actor: ActorStateProof,
+    proposal: Proof,
+    state: Proof,
+}
+
+impl DealProof {
+    /// Verifies the proof against the state root and returns the deal, decoding its state with
+    /// the layout of the market version of the state.
+    pub fn verify(&self, state_root: &Cid, version: MarketVersion) -> Result<Deal> {
+        self.verify_with(state_root, version, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the deal, decoding its state with the layout of the market version.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        version: MarketVersion,
+        hasher: &H,
+    ) -> Result<Deal> {
+        let market = self.actor.verify_with(state_root, hasher)?;
+        if self.actor.address().id() != Some(STORAGE_MARKET_ACTOR_ID) {
+            return Err(Error::InvalidNode("actor is not the storage market actor".into()).into());
+        }
+
+        let proposal = verify_amt_field(
+            &self.proposal,
+            &market.head,
+            PROPOSALS_FIELD,
+            self.deal_id,
+            hasher,
+        )?
+        .ok_or(Error::KeyNotFound)?;
+        let state = verify_amt_field(
+            &self.state,
+            &market.head,
+            STATES_FIELD,
+            self.deal_id,
+            hasher,
+        )?;
+        Ok(Deal {
+            proposal: decode_value(&proposal)?,
+            state: state
+                .as_ref()
+                .map(|state| DealState::decode(state, version))
+                .transpose()?,
+        })
+    }
+
+    /// Returns the ID of the deal being proven.
+    pub fn deal_id(&self) -> u64 {
+        self.deal_id
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the proposal and state of the storage deal with the ID.
+    pub fn generate_deal_proof(&self, state_root: &Cid, deal_id: u64) -> Result<DealProof> {
+        let addr = Address::new_id(STORAGE_MARKET_ACTOR_ID);
+        let market = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+        let proposal = self.generate_amt_field_proof(&market.head, PROPOSALS_FIELD, deal_id)?;
+        // Deals which do not exist can't be proven, only deals without a state.
+        verify_amt_field(
+            &proposal,
+            &market.head,
+            PROPOSALS_FIELD,
+            deal_id,
+            self.hasher(),
+        )?
+        .ok_or(Error::KeyNotFound)?;
+        Ok(DealProof {
+            deal_id,
+            actor: self.generate_actor_proof(state_root, &addr)?,
+            proposal,
+            state: self.generate_amt_field_proof(&market.head, STATES_FIELD, deal_id)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::{amt, DEFAULT_HASH_CODE};
+    use forest_ipld::{to_ipld, Ipld};
+    use ipld_blockstore::BlockStore;
+
+    fn proposal(deal_id: u64, piece_cid: Cid) -> DealProposal {
+        DealProposal {
+            piece_cid,
+            piece_size: 2048,
+            verified_deal: deal_id < 20,
+            client: Address::new_id(1000 + deal_id),
+            provider: Address::new_id(2000),
+            label: if deal_id > 40 {
+                Label::Bytes(vec![deal_id as u8])
+            } else {
+                Label::String(format!("deal {}", deal_id))
+            },
+            start_epoch: 100,
+            end_epoch: 200_000,
+            storage_price_per_epoch: 10,
+            provider_collateral: 1_000_000,
+            client_collateral: 0,
+        }
+    }
+
+    fn deal_state(deal_id: u64, version: MarketVersion) -> DealState {
+        DealState {
+            sector_number: match version {
+                MarketVersion::V13 => Some(deal_id),
+                _ => None,
+            },
+            sector_start_epoch: 150,
+            last_updated_epoch: -1,
+            slash_epoch: -1,
+            verified_claim: match version {
+                MarketVersion::V9 => Some(deal_id * 2),
+                _ => None,
+            },
+        }
+    }
+
+    /// Builds a state tree with the market actor, with the deal states of the market version.
+    fn build_market<BS: BlockStore>(bs: &BS, piece: Cid, version: MarketVersion) -> Cid {
+        let proposals: Vec<_> = (0..50)
+            .map(|i| to_ipld(proposal(i, piece)).unwrap())
+            .collect();
+        let states: Vec<_> = (0..40)
+            .map(|i| to_ipld(deal_state(i, version)).unwrap())
+            .collect();
+        let empty = bs.put(&Vec::<u8>::new(), DEFAULT_HASH_CODE).unwrap();
+        let market_state = Ipld::List(vec![
+            Ipld::Link(amt::build(bs, &proposals, Some(5))),
+            Ipld::Link(amt::build(bs, &states, Some(6))),
+            Ipld::Link(empty),
+            Ipld::Integer(50),
+        ]);
+        let head = bs.put(&market_state, DEFAULT_HASH_CODE).unwrap();
+        build_actors(
+            bs,
+            &[
+                (STORAGE_MARKET_ACTOR_ID, test_actor(empty, head)),
+                (100, test_actor(empty, empty)),
+            ],
+        )
+    }
+
+    #[test]
+    fn deal_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let piece = bs.put(&"piece", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_market(&bs, piece, MarketVersion::V13);
+
+        let p_gen = ProofGenerator::new(&bs);
+        for deal_id in &[0, 3, 39] {
+            let proof = p_gen.generate_deal_proof(&state_root, *deal_id).unwrap();
+            let deal = proof.verify(&state_root, MarketVersion::V13).unwrap();
+            assert_eq!(deal.proposal, proposal(*deal_id, piece));
+            assert_eq!(deal.state, Some(deal_state(*deal_id, MarketVersion::V13)));
+        }
+
+        // Deal which has not been activated proves the absence of a state.
+        let proof = p_gen.generate_deal_proof(&state_root, 45).unwrap();
+        let deal = proof.verify(&state_root, MarketVersion::V13).unwrap();
+        assert_eq!(deal.proposal, proposal(45, piece));
+        assert_eq!(deal.state, None);
+
+        // Segments for a deal in a different AMT node are rejected.
+        let other = p_gen.generate_deal_proof(&state_root, 3).unwrap();
+        let forged = DealProof {
+            deal_id: 45,
+            proposal: other.proposal,
+            ..proof
+        };
+        assert!(forged.verify(&state_root, MarketVersion::V13).is_err());
+
+        assert!(p_gen.generate_deal_proof(&state_root, 50).is_err());
+        // Actor other than the market actor is rejected.
+        let forged = DealProof {
+            actor: p_gen
+                .generate_actor_proof(&state_root, &Address::new_id(100))
+                .unwrap(),
+            ..p_gen.generate_deal_proof(&state_root, 3).unwrap()
+        };
+        assert!(forged.verify(&state_root, MarketVersion::V13).is_err());
+    }
+
+    #[test]
+    fn deal_state_versions() {
+        let bs = forest_db::MemoryDB::default();
+        let piece = bs.put(&"piece", DEFAULT_HASH_CODE).unwrap();
+        for version in &[MarketVersion::V8, MarketVersion::V9, MarketVersion::V13] {
+            let state_root = build_market(&bs, piece, *version);
+            let p_gen = ProofGenerator::new(&bs);
+            let proof = p_gen.generate_deal_proof(&state_root, 7).unwrap();
+            let deal = proof.verify(&state_root, *version).unwrap();
+            assert_eq!(deal.state, Some(deal_state(7, *version)));
+        }
+
+        // Market v9 state of `[start, updated, slash, claim]` keeps its epochs in place.
+        let v9 = Ipld::List(vec![
+            Ipld::Integer(150),
+            Ipld::Integer(160),
+            Ipld::Integer(-1),
+            Ipld::Integer(42),
+        ]);
+        assert_eq!(
+            DealState::decode(&v9, MarketVersion::V9).unwrap(),
+            DealState {
+                sector_number: None,
+                sector_start_epoch: 150,
+                last_updated_epoch: 160,
+                slash_epoch: -1,
+                verified_claim: Some(42),
+            }
+        );
+        // Negative sector numbers are rejected rather than cast.
+        let negative = Ipld::List(vec![
+            Ipld::Integer(-1),
+            Ipld::Integer(150),
+            Ipld::Integer(160),
+            Ipld::Integer(-1),
+        ]);
+        assert!(DealState::decode(&negative, MarketVersion::V13).is_err());
+        assert!(DealState::decode(&v9, MarketVersion::V8).is_err());
+    }
+}
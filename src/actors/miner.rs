@@ -0,0 +1,602 @@
+use super::{decode_fields, decode_value, field, load_fields, verify_amt_field};
+use crate::bigint::{self, BigInt};
+use crate::bitfield::BitField;
+use crate::{
+    amt, ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+    StoragePower, TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::{tuple::*, BytesDe};
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use serde::{de, Deserialize, Deserializer};
+use std::convert::TryFrom;
+use std::fmt;
+
+/// Position of the sectors AMT in the miner actor state.
+const SECTORS_FIELD: usize = 9;
+/// Position of the link to the deadlines in the miner actor state.
+const DEADLINES_FIELD: usize = 12;
+/// Position of the partitions AMT in a deadline.
+const PARTITIONS_FIELD: usize = 0;
+
+/// Information about a sector committed by a miner.
+///
+/// Fields added by newer actor versions after `sector_key_cid` are ignored when decoding.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple)]
+pub struct SectorOnChainInfo {
+    pub sector_number: u64,
+    /// Registered seal proof type of the sector.
+    pub seal_proof: i64,
+    /// CommR of the sealed sector.
+    pub sealed_cid: Cid,
+    pub deal_ids: Vec<u64>,
+    /// Epoch the sector was activated at.
+    pub activation: i64,
+    /// Epoch the sector expires at.
+    pub expiration: i64,
+    #[serde(with = "bigint")]
+    pub deal_weight: StoragePower,
+    #[serde(with = "bigint")]
+    pub verified_deal_weight: StoragePower,
+    #[serde(with = "bigint")]
+    pub initial_pledge: TokenAmount,
+    #[serde(with = "bigint")]
+    pub expected_day_reward: TokenAmount,
+    #[serde(with = "bigint")]
+    pub expected_storage_pledge: TokenAmount,
+    /// Epoch the sector's power was last computed at, which older actors store as the age of
+    /// the replaced sector.
+    pub power_base_epoch: i64,
+    #[serde(with = "bigint")]
+    pub replaced_day_reward: TokenAmount,
+    /// CommR of the sector before it was updated, only included in newer miner states.
+    pub sector_key_cid: Option<Cid>,
+}
+
+impl<'de> Deserialize<'de> for SectorOnChainInfo {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SectorVisitor;
+        impl<'de> de::Visitor<'de> for SectorVisitor {
+            type Value = SectorOnChainInfo;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("sector on-chain info")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                macro_rules! next {
+                    ($t:ty) => {
+                        seq.next_element::<$t>()?.ok_or_else(|| {
+                            de::Error::custom("missing sector on-chain info field")
+                        })?
+                    };
+                }
+                let info = SectorOnChainInfo {
+                    sector_number: next!(u64),
+                    seal_proof: next!(i64),
+                    sealed_cid: next!(Cid),
+                    deal_ids: next!(Vec<u64>),
+                    activation: next!(i64),
+                    expiration: next!(i64),
+                    deal_weight: next!(BigInt).0,
+                    verified_deal_weight: next!(BigInt).0,
+                    initial_pledge: next!(BigInt).0,
+                    expected_day_reward: next!(BigInt).0,
+                    expected_storage_pledge: next!(BigInt).0,
+                    power_base_epoch: next!(i64),
+                    replaced_day_reward: next!(BigInt).0,
+                    sector_key_cid: seq.next_element::<Option<Cid>>()?.flatten(),
+                };
+                while seq.next_element::<de::IgnoredAny>()?.is_some() {}
+                Ok(info)
+            }
+        }
+        deserializer.deserialize_seq(SectorVisitor)
+    }
+}
+
+/// Proof of a sector's on-chain info in the sectors AMT of a miner.
+///
+/// The proof chains the miner actor's state in the state tree to a segment from the miner's
+/// state through the sectors AMT, which may prove that the sector does not exist.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct SectorProof {
+    sector_number: u64,
+    actor: ActorStateProof,
+    sector: Proof,
+}
+
+impl SectorProof {
+    /// Verifies the proof against the state root and returns the sector's info, or `None` if
+    /// the miner has no sector with the number.
+    pub fn verify(&self, state_root: &Cid) -> Result<Option<SectorOnChainInfo>> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the sector's info, or `None` if the miner has no sector with the number.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Option<SectorOnChainInfo>> {
+        let miner = self.actor.verify_with(state_root, hasher)?;
+        let value = verify_amt_field(
+            &self.sector,
+            &miner.head,
+            SECTORS_FIELD,
+            self.sector_number,
+            hasher,
+        )?;
+        let info: Option<SectorOnChainInfo> = value.as_ref().map(decode_value).transpose()?;
+        if let Some(info) = &info {
+            if info.sector_number != self.sector_number {
+                return Err(Error::InvalidNode("sector number does not match index".into()).into());
+            }
+        }
+        Ok(info)
+    }
+
+    /// Returns the address of the miner.
+    pub fn miner(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns the number of the sector being proven.
+    pub fn sector_number(&self) -> u64 {
+        self.sector_number
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the on-chain info of the miner's sector. The sector does not have
+    /// to exist, in which case the proof shows that it is not in the sectors AMT.
+    pub fn generate_sector_proof(
+        &self,
+        state_root: &Cid,
+        miner: &Address,
+        sector_number: u64,
+    ) -> Result<SectorProof> {
+        let actor = self
+            .get_actor(state_root, miner)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(SectorProof {
+            sector_number,
+            actor: self.generate_actor_proof(state_root, miner)?,
+            sector: self.generate_amt_field_proof(&actor.head, SECTORS_FIELD, sector_number)?,
+        })
+    }
+}
+
+/// Status of a sector within its partition, returned from verifying a [SectorStatusProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SectorStatus {
+    /// Index of the deadline the sector is assigned to.
+    pub deadline: u64,
+    /// Index of the partition within the deadline.
+    pub partition: u64,
+    pub terminated: bool,
+    pub faulty: bool,
+    /// Faulty sector which has been declared as recovering.
+    pub recovering: bool,
+    /// Sector which has not been proven since it was added or recovered.
+    pub unproven: bool,
+}
+
+impl SectorStatus {
+    /// Returns true if the sector has not been terminated. Live sectors may still be faulty.
+    pub fn is_live(&self) -> bool {
+        !self.terminated
+    }
+
+    /// Returns true if the sector is live, proven and not faulty.
+    pub fn is_active(&self) -> bool {
+        !self.terminated && !self.faulty && !self.unproven
+    }
+}
+
+/// Sector bitfields of a partition. Partitions of the first miner actor version, which have no
+/// unproven sectors, are not supported.
+struct Partition {
+    sectors: BitField,
+    unproven: BitField,
+    faults: BitField,
+    recoveries: BitField,
+    terminated: BitField,
+}
+
+impl Partition {
+    fn decode(value: &Ipld) -> Result<Self> {
+        let fields: Vec<Ipld> = decode_value(value)?;
+        let bitfield = |position| -> Result<BitField> {
+            BitField::from_bytes(&field::<BytesDe>(&fields, position)?.0)
+        };
+        Ok(Self {
+            sectors: bitfield(0)?,
+            unproven: bitfield(1)?,
+            faults: bitfield(2)?,
+            recoveries: bitfield(3)?,
+            terminated: bitfield(4)?,
+        })
+    }
+}
+
+/// Proof of the status of a miner's sector, from the partition it is assigned to.
+///
+/// The proof chains the miner actor's state in the state tree to a segment from the miner's
+/// state through the deadlines, the deadline and its partitions AMT, to the partition.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct SectorStatusProof {
+    sector_number: u64,
+    deadline: u64,
+    partition: u64,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl SectorStatusProof {
+    /// Verifies the proof against the state root and returns the status of the sector. The
+    /// sector must be in the partition's sectors.
+    pub fn verify(&self, state_root: &Cid) -> Result<SectorStatus> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the status of the sector.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<SectorStatus> {
+        let miner = self.actor.verify_with(state_root, hasher)?;
+        let mut nodes = self.path.nodes().iter().rev();
+        let deadlines: Cid = field(
+            &decode_fields(nodes.next(), &miner.head, hasher)?,
+            DEADLINES_FIELD,
+        )?;
+        let due: Vec<Cid> = field(&decode_fields(nodes.next(), &deadlines, hasher)?, 0)?;
+        let deadline = usize::try_from(self.deadline)
+            .ok()
+            .and_then(|i| due.get(i))
+            .ok_or_else(|| Error::InvalidNode("deadline index out of range".into()))?;
+        let partitions: Cid = field(
+            &decode_fields(nodes.next(), deadline, hasher)?,
+            PARTITIONS_FIELD,
+        )?;
+        let value =
+            amt::verify(nodes, &partitions, self.partition, hasher)?.ok_or(Error::KeyNotFound)?;
+
+        let partition = Partition::decode(&value)?;
+        let n = self.sector_number;
+        if !partition.sectors.get(n) {
+            return Err(Error::KeyNotFound.into());
+        }
+        Ok(SectorStatus {
+            deadline: self.deadline,
+            partition: self.partition,
+            terminated: partition.terminated.get(n),
+            faulty: partition.faults.get(n),
+            recovering: partition.recoveries.get(n),
+            unproven: partition.unproven.get(n),
+        })
+    }
+
+    /// Returns the address of the miner.
+    pub fn miner(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns the number of the sector being proven.
+    pub fn sector_number(&self) -> u64 {
+        self.sector_number
+    }
+}
+
+/// Loads the links to the miner's deadlines from the miner's state.
+fn load_deadlines<BS: BlockStore>(bs: &BS, head: &Cid) -> Result<Vec<Cid>> {
+    let deadlines: Cid = field(&load_fields(bs, head)?, DEADLINES_FIELD)?;
+    field(&load_fields(bs, &deadlines)?, 0)
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the status of the miner's sector. This searches all deadlines for
+    /// the partition the sector is assigned to, which must exist.
+    pub fn generate_sector_status_proof(
+        &self,
+        state_root: &Cid,
+        miner: &Address,
+        sector_number: u64,
+    ) -> Result<SectorStatusProof> {
+        let actor = self
+            .get_actor(state_root, miner)?
+            .ok_or(Error::KeyNotFound)?;
+
+        let mut found = None;
+        for (idx, deadline) in load_deadlines(self, &actor.head)?.iter().enumerate() {
+            let partitions = field(&load_fields(self, deadline)?, PARTITIONS_FIELD)?;
+            amt::for_each(self, &partitions, |partition, value| {
+                if found.is_none() && Partition::decode(value)?.sectors.get(sector_number) {
+                    found = Some((idx, partition));
+                }
+                Ok(())
+            })?;
+            if found.is_some() {
+                break;
+            }
+        }
+        let (deadline, partition) = found.ok_or(Error::KeyNotFound)?;
+
+        // Nodes of all deadlines were loaded in the search, so the path is loaded through a new
+        // generator to only connect the nodes along it.
+        let path = self.nested();
+        let due = load_deadlines(&path, &actor.head)?;
+        let partitions = field(&load_fields(&path, &due[deadline])?, PARTITIONS_FIELD)?;
+        let (_, leaf) = amt::lookup(&path, &partitions, partition)?;
+        Ok(SectorStatusProof {
+            sector_number,
+            deadline: deadline as u64,
+            partition,
+            actor: self.generate_actor_proof(state_root, miner)?,
+            path: path.generate_proof_raw(leaf, Some(&actor.head))?,
+        })
+    }
+}
+
+/// Builds a miner state with the sectors, returning the head. Other fields are filled with
+/// placeholder values of the right types.
+#[cfg(test)]
+pub(crate) fn build_miner_state<BS: BlockStore>(
+    bs: &BS,
+    sectors: &[SectorOnChainInfo],
+    deadlines: Cid,
+) -> Cid {
+    use crate::{amt, DEFAULT_HASH_CODE};
+    use forest_ipld::{to_ipld, Ipld};
+
+    let mut values = vec![Ipld::Null; sectors.last().map_or(0, |s| s.sector_number + 1) as usize];
+    for sector in sectors {
+        values[sector.sector_number as usize] = to_ipld(sector).unwrap();
+    }
+    let sectors = amt::build(bs, &values, Some(5));
+    let info = bs.put(&"miner info", DEFAULT_HASH_CODE).unwrap();
+    let zero = Ipld::Bytes(vec![]);
+    let state = Ipld::List(vec![
+        Ipld::Link(info),
+        zero.clone(),
+        zero.clone(),
+        Ipld::Link(info),
+        zero.clone(),
+        zero,
+        Ipld::Link(info),
+        Ipld::Link(info),
+        Ipld::Link(info),
+        Ipld::Link(sectors),
+        Ipld::Integer(0),
+        Ipld::Integer(0),
+        Ipld::Link(deadlines),
+        Ipld::Bytes(vec![]),
+    ]);
+    bs.put(&state, DEFAULT_HASH_CODE).unwrap()
+}
+
+#[cfg(test)]
+pub(crate) fn test_sector(sector_number: u64, sealed_cid: Cid) -> SectorOnChainInfo {
+    SectorOnChainInfo {
+        sector_number,
+        seal_proof: 8,
+        sealed_cid,
+        deal_ids: vec![sector_number * 2],
+        activation: 1000 + sector_number as i64,
+        expiration: 500_000,
+        deal_weight: 0,
+        verified_deal_weight: 1 << 40,
+        initial_pledge: 1_000_000_000,
+        expected_day_reward: 1000,
+        expected_storage_pledge: 20_000,
+        power_base_epoch: 1000,
+        replaced_day_reward: 0,
+        sector_key_cid: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::{bitfield, DEFAULT_HASH_CODE};
+    use forest_db::MemoryDB;
+
+    #[test]
+    fn sector_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let sealed = bs.put(&"sealed", DEFAULT_HASH_CODE).unwrap();
+        let sectors: Vec<_> = (0..100)
+            .filter(|n| n % 10 != 7)
+            .map(|n| test_sector(n, sealed))
+            .collect();
+        let deadlines = bs.put(&"deadlines", DEFAULT_HASH_CODE).unwrap();
+        let head = build_miner_state(&bs, &sectors, deadlines);
+        let miner = Address::new_id(1234);
+        let state_root = build_actors(&bs, &[(1234, test_actor(sealed, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        for n in &[0, 45, 99] {
+            let proof = p_gen
+                .generate_sector_proof(&state_root, &miner, *n)
+                .unwrap();
+            let info = proof.verify(&state_root).unwrap().unwrap();
+            assert_eq!(info, test_sector(*n, sealed));
+            assert_eq!(info.sealed_cid, sealed);
+            assert_eq!(info.activation, 1000 + *n as i64);
+            assert_eq!(proof.miner(), &miner);
+        }
+
+        // Sectors which are not in the AMT are proven absent.
+        for n in &[17, 100, 5000] {
+            let proof = p_gen
+                .generate_sector_proof(&state_root, &miner, *n)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap(), None);
+        }
+
+        // Proof of a sector in a different AMT node can't be used for another sector.
+        let proof = p_gen.generate_sector_proof(&state_root, &miner, 3).unwrap();
+        let forged = SectorProof {
+            sector_number: 45,
+            ..proof
+        };
+        assert!(forged.verify(&state_root).is_err());
+
+        assert!(p_gen
+            .generate_sector_proof(&state_root, &Address::new_id(1), 0)
+            .is_err());
+    }
+
+    #[test]
+    fn sector_info_versions() {
+        let sealed = cid::new_from_cbor(&[], DEFAULT_HASH_CODE);
+        let mut info = test_sector(3, sealed);
+        info.sector_key_cid = Some(sealed);
+
+        // Newer fields after the sector key are ignored.
+        let mut bz = forest_encoding::to_vec(&info).unwrap();
+        bz[0] += 2;
+        bz.extend_from_slice(&[0xf5, 0x01]);
+        assert_eq!(
+            forest_encoding::from_slice::<SectorOnChainInfo>(&bz).unwrap(),
+            info
+        );
+
+        // Older miner states do not include the sector key.
+        let mut bz = forest_encoding::to_vec(&info).unwrap();
+        bz[0] -= 1;
+        bz.truncate(bz.len() - 43);
+        info.sector_key_cid = None;
+        assert_eq!(
+            forest_encoding::from_slice::<SectorOnChainInfo>(&bz).unwrap(),
+            info
+        );
+
+        bz[0] -= 1;
+        bz.truncate(bz.len() - 1);
+        assert!(forest_encoding::from_slice::<SectorOnChainInfo>(&bz).is_err());
+    }
+
+    fn partition(bitfields: [&[u64]; 5]) -> Ipld {
+        let mut fields: Vec<_> = bitfields
+            .iter()
+            .map(|bits| Ipld::Bytes(bitfield::encode(bits)))
+            .collect();
+        let empty = cid::new_from_cbor(&[0x80], DEFAULT_HASH_CODE);
+        fields.extend(vec![Ipld::Link(empty), Ipld::Link(empty)]);
+        Ipld::List(fields)
+    }
+
+    /// Builds the deadlines from the partitions of each deadline, returning the Cid of the
+    /// deadlines.
+    fn build_deadlines(bs: &MemoryDB, partitions: &[(usize, Vec<Ipld>)]) -> Cid {
+        let mut due = Vec::new();
+        for idx in 0..48 {
+            let values = partitions
+                .iter()
+                .find(|(d, _)| *d == idx)
+                .map(|(_, p)| p.clone())
+                .unwrap_or_default();
+            let deadline = Ipld::List(vec![
+                Ipld::Link(amt::build(bs, &values, Some(3))),
+                Ipld::Link(cid::new_from_cbor(&[0x80], DEFAULT_HASH_CODE)),
+                Ipld::Bytes(vec![]),
+                Ipld::Bytes(vec![]),
+                Ipld::Integer(0),
+                Ipld::Integer(0),
+            ]);
+            due.push(Ipld::Link(bs.put(&deadline, DEFAULT_HASH_CODE).unwrap()));
+        }
+        bs.put(&Ipld::List(vec![Ipld::List(due)]), DEFAULT_HASH_CODE)
+            .unwrap()
+    }
+
+    #[test]
+    fn sector_status_proofs() {
+        let bs = MemoryDB::default();
+        let sectors: Vec<u64> = (0..10).collect();
+        let full: Vec<u64> = (10..20).chain(std::iter::once(100)).collect();
+        let deadlines = build_deadlines(
+            &bs,
+            &[
+                (
+                    3,
+                    vec![
+                        partition([&sectors, &[9], &[5, 6], &[6], &[2]]),
+                        partition([&full, &[], &[], &[], &[]]),
+                    ],
+                ),
+                (10, vec![partition([&[30, 31, 32], &[], &[], &[], &[]])]),
+            ],
+        );
+        let sealed = bs.put(&"sealed", DEFAULT_HASH_CODE).unwrap();
+        let head = build_miner_state(&bs, &[test_sector(0, sealed)], deadlines);
+        let miner = Address::new_id(1234);
+        let state_root = build_actors(&bs, &[(1234, test_actor(sealed, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        let status = |n| {
+            let proof = p_gen
+                .generate_sector_status_proof(&state_root, &miner, n)
+                .unwrap();
+            proof.verify(&state_root).unwrap()
+        };
+
+        let active = status(0);
+        assert_eq!((active.deadline, active.partition), (3, 0));
+        assert!(active.is_live() && active.is_active());
+        let terminated = status(2);
+        assert!(!terminated.is_live() && !terminated.is_active());
+        let faulty = status(5);
+        assert!(faulty.is_live() && !faulty.is_active() && !faulty.recovering);
+        assert!(status(6).recovering);
+        let unproven = status(9);
+        assert!(unproven.unproven && !unproven.is_active());
+        let other = status(100);
+        assert_eq!((other.deadline, other.partition), (3, 1));
+        assert!(other.is_active());
+        assert_eq!(status(31).deadline, 10);
+
+        // Sectors which are not assigned to any partition can't be proven.
+        assert!(p_gen
+            .generate_sector_status_proof(&state_root, &miner, 25)
+            .is_err());
+
+        // Proof of a partition can't be used for a sector outside of it.
+        let proof = p_gen
+            .generate_sector_status_proof(&state_root, &miner, 3)
+            .unwrap();
+        let forged = SectorStatusProof {
+            sector_number: 15,
+            ..proof
+        };
+        assert!(forged.verify(&state_root).is_err());
+        let forged = SectorStatusProof {
+            sector_number: 3,
+            partition: 1,
+            ..forged
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
@@ -0,0 +1,160 @@
+//! Typed proofs of data within the state of the builtin actors. Actor states are decoded by the
+//! position of their fields, so that the proofs are compatible with all actor versions which
+//! keep the fields used at the same position.
+
+mod datacap;
+mod eth;
+mod evm;
+mod init;
+mod market;
+mod miner;
+mod multisig;
+mod paych;
+mod power;
+mod system;
+mod verifreg;
+
+pub use self::datacap::*;
+pub use self::eth::*;
+pub use self::evm::*;
+pub use self::init::*;
+pub use self::market::*;
+pub use self::miner::*;
+pub use self::multisig::*;
+pub use self::paych::*;
+pub use self::power::*;
+pub use self::system::*;
+pub use self::verifreg::*;
+
+use crate::{
+    amt, check_cid_with, hamt, load_bytes, Address, Error, Proof, ProofGenerator, ProofHasher,
+};
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use forest_encoding::de::DeserializeOwned;
+use forest_ipld::Ipld;
+use integer_encoding::VarInt;
+use ipld_blockstore::BlockStore;
+
+/// Checks the node against its expected Cid and decodes the fields of the actor state, or of a
+/// structure linked from it.
+fn decode_fields<H: ProofHasher + ?Sized>(
+    node: Option<&Vec<u8>>,
+    cid: &Cid,
+    hasher: &H,
+) -> Result<Vec<Ipld>> {
+    let node = node.ok_or(Error::IncompleteProof)?;
+    check_cid_with(node, cid, hasher)?;
+    Ok(forest_encoding::from_slice(node)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode actor state: {}", e)))?)
+}
+
+/// Encodes the integer as a HAMT key, which the builtin actors encode as an unsigned varint.
+fn u64_key(k: u64) -> Vec<u8> {
+    k.encode_var_vec()
+}
+
+/// Encodes the signed integer as a HAMT key, which the builtin actors encode as a zigzag signed
+/// varint, such as the IDs of multisig transactions.
+fn i64_key(k: i64) -> Vec<u8> {
+    k.encode_var_vec()
+}
+
+/// Encodes the ID of the address as a HAMT key, for maps which are keyed by actor ID.
+fn id_key(addr: &Address) -> Result<Vec<u8>> {
+    let id = addr
+        .id()
+        .ok_or(Error::InvalidAddress("expected an ID address"))?;
+    Ok(u64_key(id))
+}
+
+/// Loads the node from the store and decodes its fields.
+fn load_fields<BS: BlockStore>(bs: &BS, cid: &Cid) -> Result<Vec<Ipld>> {
+    Ok(forest_encoding::from_slice(&load_bytes(bs, cid)?)?)
+}
+
+/// Decodes the field of an actor state at the position.
+fn field<T: DeserializeOwned>(fields: &[Ipld], position: usize) -> Result<T> {
+    let value = fields
+        .get(position)
+        .ok_or_else(|| Error::InvalidNode(format!("actor state has no field {}", position)))?;
+    forest_ipld::from_ipld(value).map_err(|e| anyhow!(e))
+}
+
+/// Decodes a value of an actor's AMT or HAMT.
+fn decode_value<T: DeserializeOwned>(value: &Ipld) -> Result<T> {
+    forest_ipld::from_ipld(value)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode value: {}", e)).into())
+}
+
+/// Verifies the proof segment, which starts at the actor's head, is the path to the index of
+/// the AMT at the field of the actor state.
+fn verify_amt_field<H: ProofHasher + ?Sized>(
+    proof: &Proof,
+    head: &Cid,
+    position: usize,
+    index: u64,
+    hasher: &H,
+) -> Result<Option<Ipld>> {
+    let mut nodes = proof.nodes().iter().rev();
+    let root = field(&decode_fields(nodes.next(), head, hasher)?, position)?;
+    amt::verify(nodes, &root, index, hasher)
+}
+
+/// Verifies the proof segment, which starts at the actor's head, is the path to the key of the
+/// HAMT at the field of the actor state.
+fn verify_hamt_field<H: ProofHasher + ?Sized>(
+    proof: &Proof,
+    head: &Cid,
+    position: usize,
+    key: &[u8],
+    hasher: &H,
+) -> Result<Option<Ipld>> {
+    let mut nodes = proof.nodes().iter().rev();
+    let root = field(&decode_fields(nodes.next(), head, hasher)?, position)?;
+    hamt::verify(nodes, &root, key, hamt::DEFAULT_BIT_WIDTH, hasher)
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof segment from the actor's head through the AMT at the field of the actor
+    /// state, to the index of the AMT. The index does not have to exist.
+    fn generate_amt_field_proof(&self, head: &Cid, position: usize, index: u64) -> Result<Proof> {
+        let root = field(&load_fields(self, head)?, position)?;
+        let (_, leaf) = amt::lookup(self, &root, index)?;
+        self.generate_proof_raw(leaf, Some(head))
+    }
+
+    /// Generates a proof segment from the actor's head through the HAMT at the field of the
+    /// actor state, to the key of the HAMT. The key does not have to exist.
+    fn generate_hamt_field_proof(&self, head: &Cid, position: usize, key: &[u8]) -> Result<Proof> {
+        let root = field(&load_fields(self, head)?, position)?;
+        let (_, leaf) = hamt::lookup(self, &root, key, hamt::DEFAULT_BIT_WIDTH)?;
+        self.generate_proof_raw(leaf, Some(head))
+    }
+}
+
+/// Returns the state of an actor with the code and head, with no balance or sequence.
+#[cfg(test)]
+pub(crate) fn test_actor(code: Cid, head: Cid) -> crate::ActorState {
+    crate::ActorState {
+        code,
+        head,
+        sequence: 0,
+        balance: 0,
+        delegated_address: None,
+    }
+}
+
+/// Builds a state tree with each actor at its ID, returning the state root.
+#[cfg(test)]
+pub(crate) fn build_actors<BS: BlockStore>(bs: &BS, actors: &[(u64, crate::ActorState)]) -> Cid {
+    let actors: Vec<_> = actors
+        .iter()
+        .map(|(id, actor)| (Address::new_id(*id), actor.clone()))
+        .collect();
+    crate::build_state_tree(bs, &actors)
+}
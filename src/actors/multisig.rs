@@ -0,0 +1,211 @@
+use super::{decode_fields, decode_value, field, i64_key, verify_hamt_field};
+use crate::bigint;
+use crate::{
+    ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher, TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::{serde_bytes, tuple::*};
+use ipld_blockstore::BlockStore;
+
+/// Position of the signers in the multisig actor state.
+const SIGNERS_FIELD: usize = 0;
+/// Position of the number of approvals required in the multisig actor state.
+const THRESHOLD_FIELD: usize = 1;
+/// Position of the pending transactions HAMT in the multisig actor state.
+const PENDING_TXS_FIELD: usize = 6;
+
+/// Transaction proposed to a multisig which has not been executed.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct MultisigTransaction {
+    pub to: Address,
+    #[serde(with = "bigint")]
+    pub value: TokenAmount,
+    pub method: u64,
+    #[serde(with = "serde_bytes")]
+    pub params: Vec<u8>,
+    /// Signers which have approved the transaction, starting with the proposer.
+    pub approved: Vec<Address>,
+}
+
+/// Pending transaction of a multisig along with its signers, returned from verifying a
+/// [MultisigTxnProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultisigTxn {
+    /// Transaction, or `None` if no transaction with the ID is pending.
+    pub transaction: Option<MultisigTransaction>,
+    pub signers: Vec<Address>,
+    /// Number of approvals required to execute a transaction.
+    pub threshold: u64,
+}
+
+impl MultisigTxn {
+    /// Returns true if the transaction has the approvals of enough current signers to be
+    /// executed.
+    pub fn is_approved(&self) -> bool {
+        let txn = match &self.transaction {
+            Some(txn) => txn,
+            None => return false,
+        };
+        let approvals = txn
+            .approved
+            .iter()
+            .filter(|a| self.signers.contains(a))
+            .count();
+        approvals as u64 >= self.threshold
+    }
+}
+
+/// Proof of a pending transaction of a multisig, along with its signers and threshold.
+///
+/// The proof chains the multisig's state in the state tree to a segment from the multisig's
+/// state through the pending transactions HAMT, which may prove the transaction is not pending.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct MultisigTxnProof {
+    txn_id: i64,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl MultisigTxnProof {
+    /// Verifies the proof against the state root and returns the pending transaction along with
+    /// the signers of the multisig.
+    pub fn verify(&self, state_root: &Cid) -> Result<MultisigTxn> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the pending transaction along with the signers of the multisig.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<MultisigTxn> {
+        let msig = self.actor.verify_with(state_root, hasher)?;
+        let txn = verify_hamt_field(
+            &self.path,
+            &msig.head,
+            PENDING_TXS_FIELD,
+            &i64_key(self.txn_id),
+            hasher,
+        )?;
+        let fields = decode_fields(self.path.nodes().last(), &msig.head, hasher)?;
+        Ok(MultisigTxn {
+            transaction: txn.as_ref().map(decode_value).transpose()?,
+            signers: field(&fields, SIGNERS_FIELD)?,
+            threshold: field(&fields, THRESHOLD_FIELD)?,
+        })
+    }
+
+    /// Returns the address of the multisig.
+    pub fn multisig(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns the ID of the transaction.
+    pub fn txn_id(&self) -> i64 {
+        self.txn_id
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the pending transaction of the multisig, along with its signers.
+    /// The transaction does not have to be pending.
+    pub fn generate_multisig_txn_proof(
+        &self,
+        state_root: &Cid,
+        multisig: &Address,
+        txn_id: i64,
+    ) -> Result<MultisigTxnProof> {
+        let msig = self
+            .get_actor(state_root, multisig)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(MultisigTxnProof {
+            txn_id,
+            actor: self.generate_actor_proof(state_root, multisig)?,
+            path: self.generate_hamt_field_proof(
+                &msig.head,
+                PENDING_TXS_FIELD,
+                &i64_key(txn_id),
+            )?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::bigint::BigInt;
+    use crate::{hamt, DEFAULT_HASH_CODE};
+    use forest_ipld::{to_ipld, Ipld};
+
+    #[test]
+    fn txn_keys() {
+        // Transaction IDs are zigzag encoded, as by Go's `binary.PutVarint`.
+        assert_eq!(i64_key(0), vec![0x00]);
+        assert_eq!(i64_key(4), vec![0x08]);
+        assert_eq!(i64_key(-1), vec![0x01]);
+        assert_eq!(i64_key(100), vec![0xc8, 0x01]);
+    }
+
+    #[test]
+    fn multisig_txn_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let signers: Vec<_> = (100..103).map(Address::new_id).collect();
+        let txn = |id: u64, approvals: usize| MultisigTransaction {
+            to: Address::new_id(2000 + id),
+            value: (id as i128) << 60,
+            method: 0,
+            params: vec![],
+            approved: signers[..approvals].to_vec(),
+        };
+        let pending: Vec<_> = (0..40)
+            .map(|id| {
+                let value = to_ipld(txn(id, 1 + id as usize % 2)).unwrap();
+                (i64_key(id as i64), value)
+            })
+            .collect();
+        let pending = hamt::build(&bs, &pending, hamt::DEFAULT_BIT_WIDTH);
+        let state = Ipld::List(vec![
+            to_ipld(&signers).unwrap(),
+            Ipld::Integer(2),
+            Ipld::Integer(40),
+            to_ipld(BigInt(0)).unwrap(),
+            Ipld::Integer(0),
+            Ipld::Integer(0),
+            Ipld::Link(pending),
+        ]);
+        let head = bs.put(&state, DEFAULT_HASH_CODE).unwrap();
+        let multisig = Address::new_id(1200);
+        let code = bs.put(&"multisig", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(&bs, &[(1200, test_actor(code, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        for id in &[4, 7] {
+            let proof = p_gen
+                .generate_multisig_txn_proof(&state_root, &multisig, *id)
+                .unwrap();
+            let msig = proof.verify(&state_root).unwrap();
+            assert_eq!(
+                msig.transaction,
+                Some(txn(*id as u64, 1 + *id as usize % 2))
+            );
+            assert_eq!(msig.signers, signers);
+            assert_eq!(msig.threshold, 2);
+            assert_eq!(msig.is_approved(), id % 2 == 1);
+        }
+
+        // Executed or cancelled transactions are proven absent.
+        let proof = p_gen
+            .generate_multisig_txn_proof(&state_root, &multisig, 40)
+            .unwrap();
+        let msig = proof.verify(&state_root).unwrap();
+        assert_eq!(msig.transaction, None);
+        assert!(!msig.is_approved());
+    }
+}
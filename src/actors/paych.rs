@@ -0,0 +1,183 @@
+use super::{decode_fields, decode_value, field, verify_amt_field};
+use crate::bigint::{self, BigInt};
+use crate::{
+    ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher, TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use ipld_blockstore::BlockStore;
+
+/// Position of the lane states AMT in the payment channel actor state.
+const LANE_STATES_FIELD: usize = 5;
+
+/// State of a lane of a payment channel.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct LaneState {
+    /// Total amount redeemed by vouchers of the lane.
+    #[serde(with = "bigint")]
+    pub redeemed: TokenAmount,
+    /// Nonce of the last voucher redeemed in the lane.
+    pub nonce: u64,
+}
+
+/// State of a payment channel and one of its lanes, returned from verifying a
+/// [PaymentChannelProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaymentChannel {
+    pub from: Address,
+    pub to: Address,
+    /// Amount to be sent to `to` when the channel is settled.
+    pub to_send: TokenAmount,
+    /// Epoch the channel can be collected at, or zero if the channel is not settling.
+    pub settling_at: i64,
+    /// Minimum epoch the channel can be settled at.
+    pub min_settle_height: i64,
+    /// State of the lane, or `None` if no voucher of the lane has been redeemed.
+    pub lane: Option<LaneState>,
+}
+
+/// Proof of the state of a payment channel and one of its lanes.
+///
+/// The proof chains the payment channel's state in the state tree to a segment from the
+/// channel's state through the lane states AMT, which may prove the lane does not exist.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct PaymentChannelProof {
+    lane: u64,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl PaymentChannelProof {
+    /// Verifies the proof against the state root and returns the state of the channel and lane.
+    pub fn verify(&self, state_root: &Cid) -> Result<PaymentChannel> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the state of the channel and lane.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<PaymentChannel> {
+        let paych = self.actor.verify_with(state_root, hasher)?;
+        let lane = verify_amt_field(
+            &self.path,
+            &paych.head,
+            LANE_STATES_FIELD,
+            self.lane,
+            hasher,
+        )?;
+        let fields = decode_fields(self.path.nodes().last(), &paych.head, hasher)?;
+        Ok(PaymentChannel {
+            from: field(&fields, 0)?,
+            to: field(&fields, 1)?,
+            to_send: field::<BigInt>(&fields, 2)?.0,
+            settling_at: field(&fields, 3)?,
+            min_settle_height: field(&fields, 4)?,
+            lane: lane.as_ref().map(decode_value).transpose()?,
+        })
+    }
+
+    /// Returns the address of the payment channel.
+    pub fn channel(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns the lane being proven.
+    pub fn lane(&self) -> u64 {
+        self.lane
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the state of the payment channel and its lane. The lane does not
+    /// have to exist.
+    pub fn generate_payment_channel_proof(
+        &self,
+        state_root: &Cid,
+        channel: &Address,
+        lane: u64,
+    ) -> Result<PaymentChannelProof> {
+        let paych = self
+            .get_actor(state_root, channel)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(PaymentChannelProof {
+            lane,
+            actor: self.generate_actor_proof(state_root, channel)?,
+            path: self.generate_amt_field_proof(&paych.head, LANE_STATES_FIELD, lane)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::{amt, DEFAULT_HASH_CODE};
+    use forest_ipld::{to_ipld, Ipld};
+
+    #[test]
+    fn payment_channel_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let lane = |i: u64| LaneState {
+            redeemed: (i as i128 + 1) << 50,
+            nonce: i * 2,
+        };
+        // Every third lane has no redeemed vouchers.
+        let lanes: Vec<_> = (0..30)
+            .map(|i| {
+                if i % 3 == 1 {
+                    Ipld::Null
+                } else {
+                    to_ipld(lane(i)).unwrap()
+                }
+            })
+            .collect();
+        let lanes = amt::build(&bs, &lanes, Some(3));
+        let state = Ipld::List(vec![
+            Ipld::Bytes(Address::new_id(100).to_bytes()),
+            Ipld::Bytes(Address::new_id(101).to_bytes()),
+            to_ipld(BigInt(1 << 62)).unwrap(),
+            Ipld::Integer(0),
+            Ipld::Integer(1200),
+            Ipld::Link(lanes),
+        ]);
+        let head = bs.put(&state, DEFAULT_HASH_CODE).unwrap();
+        let channel = Address::new_id(1300);
+        let code = bs.put(&"paych", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(&bs, &[(1300, test_actor(code, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen
+            .generate_payment_channel_proof(&state_root, &channel, 12)
+            .unwrap();
+        assert_eq!(
+            proof.verify(&state_root).unwrap(),
+            PaymentChannel {
+                from: Address::new_id(100),
+                to: Address::new_id(101),
+                to_send: 1 << 62,
+                settling_at: 0,
+                min_settle_height: 1200,
+                lane: Some(lane(12)),
+            }
+        );
+
+        for i in &[13, 100] {
+            let proof = p_gen
+                .generate_payment_channel_proof(&state_root, &channel, *i)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap().lane, None);
+        }
+
+        // Proof nodes do not include the lanes of other leaves.
+        let forged = PaymentChannelProof { lane: 27, ..proof };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
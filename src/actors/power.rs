@@ -0,0 +1,240 @@
+use super::{decode_fields, decode_value, field, verify_hamt_field};
+use crate::bigint::{self, BigInt};
+use crate::{
+    ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+    StoragePower, TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use ipld_blockstore::BlockStore;
+
+/// ID of the storage power actor.
+pub const STORAGE_POWER_ACTOR_ID: u64 = 4;
+
+/// Position of the claims HAMT in the power actor state.
+const CLAIMS_FIELD: usize = 13;
+
+/// Power claimed by a miner.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct Claim {
+    /// Window PoSt proof type of the miner.
+    pub window_post_proof_type: i64,
+    #[serde(with = "bigint")]
+    pub raw_byte_power: StoragePower,
+    #[serde(with = "bigint")]
+    pub quality_adj_power: StoragePower,
+}
+
+/// Total power and pledge of the network, from the power actor's state.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PowerTotals {
+    /// Raw byte power of miners above the minimum power.
+    pub raw_byte_power: StoragePower,
+    /// Raw bytes committed by all miners.
+    pub bytes_committed: StoragePower,
+    /// Quality adjusted power of miners above the minimum power.
+    pub quality_adj_power: StoragePower,
+    /// Quality adjusted bytes committed by all miners.
+    pub qa_bytes_committed: StoragePower,
+    pub pledge_collateral: TokenAmount,
+}
+
+/// Power of a miner, returned from verifying a [PowerClaimProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerPower {
+    /// Claim of the miner, or `None` if the miner has no claim.
+    pub claim: Option<Claim>,
+    pub total: PowerTotals,
+}
+
+impl MinerPower {
+    /// Returns the miner's share of the network's quality adjusted power, from 0 to 1.
+    pub fn quality_adj_share(&self) -> f64 {
+        match &self.claim {
+            Some(claim) if self.total.quality_adj_power > 0 => {
+                claim.quality_adj_power as f64 / self.total.quality_adj_power as f64
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Proof of a miner's power claim and the network's total power.
+///
+/// The proof chains the power actor's state in the state tree to a segment from the power
+/// actor's state, which holds the total power, through the claims HAMT to the miner's claim.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct PowerClaimProof {
+    miner: Address,
+    actor: ActorStateProof,
+    claim: Proof,
+}
+
+impl PowerClaimProof {
+    /// Verifies the proof against the state root and returns the power of the miner.
+    pub fn verify(&self, state_root: &Cid) -> Result<MinerPower> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the power of the miner.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<MinerPower> {
+        let power = self.actor.verify_with(state_root, hasher)?;
+        if self.actor.address().id() != Some(STORAGE_POWER_ACTOR_ID) {
+            return Err(Error::InvalidNode("actor is not the storage power actor".into()).into());
+        }
+
+        let claim = verify_hamt_field(
+            &self.claim,
+            &power.head,
+            CLAIMS_FIELD,
+            &self.miner.to_bytes(),
+            hasher,
+        )?;
+        let fields = decode_fields(self.claim.nodes().last(), &power.head, hasher)?;
+        let big = |position| -> Result<StoragePower> { Ok(field::<BigInt>(&fields, position)?.0) };
+        Ok(MinerPower {
+            claim: claim.as_ref().map(decode_value).transpose()?,
+            total: PowerTotals {
+                raw_byte_power: big(0)?,
+                bytes_committed: big(1)?,
+                quality_adj_power: big(2)?,
+                qa_bytes_committed: big(3)?,
+                pledge_collateral: big(4)?,
+            },
+        })
+    }
+
+    /// Returns the address of the miner.
+    pub fn miner(&self) -> &Address {
+        &self.miner
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the miner's power claim, along with the network's total power. The
+    /// miner does not have to have a claim.
+    pub fn generate_power_claim_proof(
+        &self,
+        state_root: &Cid,
+        miner: &Address,
+    ) -> Result<PowerClaimProof> {
+        let addr = Address::new_id(STORAGE_POWER_ACTOR_ID);
+        let power = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(PowerClaimProof {
+            miner: miner.clone(),
+            actor: self.generate_actor_proof(state_root, &addr)?,
+            claim: self.generate_hamt_field_proof(&power.head, CLAIMS_FIELD, &miner.to_bytes())?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::{hamt, DEFAULT_HASH_CODE};
+    use forest_ipld::{to_ipld, Ipld};
+
+    fn claim(id: u64) -> Claim {
+        Claim {
+            window_post_proof_type: 8,
+            raw_byte_power: (id as i128) << 35,
+            quality_adj_power: (id as i128) << 36,
+        }
+    }
+
+    #[test]
+    fn power_claim_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let claims: Vec<_> = (1000..1100)
+            .map(|id| {
+                let addr = Address::new_id(id);
+                (addr.to_bytes(), to_ipld(claim(id)).unwrap())
+            })
+            .collect();
+        let claims = hamt::build(&bs, &claims, hamt::DEFAULT_BIT_WIDTH);
+        let big = |v: i128| to_ipld(BigInt(v)).unwrap();
+        let queue = bs.put(&"cron", DEFAULT_HASH_CODE).unwrap();
+        let mut state: Vec<_> = [1 << 50, 2 << 50, 3 << 50, 4 << 50, 5000]
+            .iter()
+            .map(|v| big(*v))
+            .collect();
+        state.extend(vec![
+            big(0),
+            big(0),
+            big(0),
+            Ipld::List(vec![big(0), big(0)]),
+            Ipld::Integer(100),
+            Ipld::Integer(90),
+            Ipld::Link(queue),
+            Ipld::Integer(0),
+            Ipld::Link(claims),
+            Ipld::Null,
+        ]);
+        let head = bs.put(&Ipld::List(state), DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(
+            &bs,
+            &[
+                (STORAGE_POWER_ACTOR_ID, test_actor(queue, head)),
+                (1000, test_actor(queue, queue)),
+            ],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        let miner = Address::new_id(1050);
+        let proof = p_gen
+            .generate_power_claim_proof(&state_root, &miner)
+            .unwrap();
+        let power = proof.verify(&state_root).unwrap();
+        assert_eq!(power.claim, Some(claim(1050)));
+        assert_eq!(power.total.raw_byte_power, 1 << 50);
+        assert_eq!(power.total.qa_bytes_committed, 4 << 50);
+        assert_eq!(power.total.pledge_collateral, 5000);
+        assert_eq!(power.quality_adj_share(), 1050.0 / (3 << 14) as f64);
+
+        // Miners without a claim have no power.
+        let proof = p_gen
+            .generate_power_claim_proof(&state_root, &Address::new_id(5))
+            .unwrap();
+        let power = proof.verify(&state_root).unwrap();
+        assert_eq!(power.claim, None);
+        assert_eq!(power.quality_adj_share(), 0.0);
+
+        // Claim segment can only prove the claim of the miner it was generated for.
+        for id in 1000..1100 {
+            let forged = PowerClaimProof {
+                miner: Address::new_id(id),
+                ..p_gen
+                    .generate_power_claim_proof(&state_root, &miner)
+                    .unwrap()
+            };
+            match forged.verify(&state_root) {
+                Ok(power) => assert_eq!(power.claim, Some(claim(id))),
+                Err(_) => assert_ne!(id, 1050),
+            }
+        }
+
+        // Actor other than the power actor is rejected.
+        let forged = PowerClaimProof {
+            actor: p_gen
+                .generate_actor_proof(&state_root, &Address::new_id(1000))
+                .unwrap(),
+            ..p_gen
+                .generate_power_claim_proof(&state_root, &miner)
+                .unwrap()
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
@@ -0,0 +1,236 @@
+use super::{decode_fields, field, load_fields};
+use crate::{
+    check_cid_with, load_bytes, ActorState, ActorStateProof, Address, Blake2bHasher, Error, Proof,
+    ProofGenerator, ProofHasher,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use ipld_blockstore::BlockStore;
+
+/// ID of the system actor.
+pub const SYSTEM_ACTOR_ID: u64 = 0;
+
+/// Position of the builtin actors manifest in the system actor state.
+const BUILTIN_ACTORS_FIELD: usize = 0;
+
+/// Manifest of the builtin actors of a network version, which maps the name of each actor type
+/// to its code [Cid].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    entries: Vec<(String, Cid)>,
+}
+
+impl Manifest {
+    /// Returns the name of the actor type with the code, such as `miner`, `account` or `evm`.
+    pub fn actor_type(&self, code: &Cid) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, c)| c == code)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Returns the code of the actor type with the name.
+    pub fn code(&self, name: &str) -> Option<&Cid> {
+        self.entries.iter().find(|(n, _)| n == name).map(|(_, c)| c)
+    }
+
+    /// Returns the names and codes of all actor types, in the order of the manifest.
+    pub fn entries(&self) -> &[(String, Cid)] {
+        &self.entries
+    }
+}
+
+/// Proof of the builtin actors manifest of a state tree.
+///
+/// The proof chains the system actor's state in the state tree to a segment from the system
+/// actor's state to the manifest it links to.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ManifestProof {
+    system: ActorStateProof,
+    manifest: Proof,
+}
+
+impl ManifestProof {
+    /// Verifies the proof against the state root and returns the manifest.
+    pub fn verify(&self, state_root: &Cid) -> Result<Manifest> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the manifest.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Manifest> {
+        let system = self.system.verify_with(state_root, hasher)?;
+        if self.system.address().id() != Some(SYSTEM_ACTOR_ID) {
+            return Err(Error::InvalidNode("actor is not the system actor".into()).into());
+        }
+
+        let mut nodes = self.manifest.nodes().iter().rev();
+        let root: Cid = field(
+            &decode_fields(nodes.next(), &system.head, hasher)?,
+            BUILTIN_ACTORS_FIELD,
+        )?;
+        let node = nodes.next().ok_or(Error::IncompleteProof)?;
+        check_cid_with(node, &root, hasher)?;
+        if nodes.next().is_some() {
+            return Err(Error::UnexpectedNodes.into());
+        }
+        let entries = forest_encoding::from_slice(node)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode manifest: {}", e)))?;
+        Ok(Manifest { entries })
+    }
+
+    /// Returns reference to the segment proving the system actor's state.
+    pub fn system_proof(&self) -> &ActorStateProof {
+        &self.system
+    }
+}
+
+/// Proof of the builtin actor type of an actor, which chains the proof of the actor's state with
+/// the proof of the manifest its code is resolved from.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ActorTypeProof {
+    actor: ActorStateProof,
+    manifest: ManifestProof,
+}
+
+impl ActorTypeProof {
+    /// Verifies the proof against the state root and returns the state of the actor along with
+    /// the name of its actor type. Actors with a code which is not in the manifest are rejected.
+    pub fn verify(&self, state_root: &Cid) -> Result<(ActorState, String)> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the state of the actor along with the name of its actor type.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<(ActorState, String)> {
+        let actor = self.actor.verify_with(state_root, hasher)?;
+        let manifest = self.manifest.verify_with(state_root, hasher)?;
+        let name = manifest
+            .actor_type(&actor.code)
+            .ok_or_else(|| Error::InvalidNode(format!("code {} is not builtin", actor.code)))?
+            .to_owned();
+        Ok((actor, name))
+    }
+
+    /// Returns the address of the actor.
+    pub fn address(&self) -> &Address {
+        self.actor.address()
+    }
+
+    /// Returns reference to the segment proving the actor's state.
+    pub fn actor_proof(&self) -> &ActorStateProof {
+        &self.actor
+    }
+
+    /// Returns reference to the segment proving the manifest.
+    pub fn manifest_proof(&self) -> &ManifestProof {
+        &self.manifest
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the builtin actors manifest linked from the system actor.
+    pub fn generate_manifest_proof(&self, state_root: &Cid) -> Result<ManifestProof> {
+        let addr = Address::new_id(SYSTEM_ACTOR_ID);
+        let system = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+        let root = field(&load_fields(self, &system.head)?, BUILTIN_ACTORS_FIELD)?;
+        let manifest = load_bytes(self, &root)?;
+        Ok(ManifestProof {
+            system: self.generate_actor_proof(state_root, &addr)?,
+            manifest: self.generate_proof_raw(manifest, Some(&system.head))?,
+        })
+    }
+
+    /// Generates a proof of the builtin actor type of the actor.
+    pub fn generate_actor_type_proof(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+    ) -> Result<ActorTypeProof> {
+        Ok(ActorTypeProof {
+            actor: self.generate_actor_proof(state_root, addr)?,
+            manifest: self.generate_manifest_proof(state_root)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::DEFAULT_HASH_CODE;
+    use forest_ipld::Ipld;
+
+    #[test]
+    fn actor_type_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let names = ["system", "init", "account", "miner", "evm"];
+        let entries: Vec<_> = names
+            .iter()
+            .map(|name| {
+                let code = bs
+                    .put(&format!("code {}", name), DEFAULT_HASH_CODE)
+                    .unwrap();
+                (name.to_string(), code)
+            })
+            .collect();
+        let manifest = bs.put(&entries, DEFAULT_HASH_CODE).unwrap();
+        let head = bs
+            .put(&Ipld::List(vec![Ipld::Link(manifest)]), DEFAULT_HASH_CODE)
+            .unwrap();
+        let empty = bs.put(&Ipld::List(vec![]), DEFAULT_HASH_CODE).unwrap();
+        let unknown = bs.put(&"unknown", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(
+            &bs,
+            &[
+                (SYSTEM_ACTOR_ID, test_actor(entries[0].1, head)),
+                (1000, test_actor(entries[3].1, empty)),
+                (1001, test_actor(unknown, empty)),
+            ],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_manifest_proof(&state_root).unwrap();
+        let verified = proof.verify(&state_root).unwrap();
+        assert_eq!(verified.entries(), entries.as_slice());
+        assert_eq!(verified.code("evm"), Some(&entries[4].1));
+        assert_eq!(verified.actor_type(&unknown), None);
+
+        let proof = p_gen
+            .generate_actor_type_proof(&state_root, &Address::new_id(1000))
+            .unwrap();
+        let (state, name) = proof.verify(&state_root).unwrap();
+        assert_eq!(state.code, entries[3].1);
+        assert_eq!(name, "miner");
+
+        // Actors with a code outside the manifest have no builtin type.
+        let proof = p_gen
+            .generate_actor_type_proof(&state_root, &Address::new_id(1001))
+            .unwrap();
+        assert!(proof.verify(&state_root).is_err());
+
+        // Manifest must be linked from the system actor.
+        let forged = ManifestProof {
+            system: p_gen
+                .generate_actor_proof(&state_root, &Address::new_id(1000))
+                .unwrap(),
+            manifest: p_gen.generate_manifest_proof(&state_root).unwrap().manifest,
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
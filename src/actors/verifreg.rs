@@ -0,0 +1,387 @@
+use super::{decode_fields, decode_value, field, id_key, load_fields, u64_key};
+use crate::{
+    hamt, ActorStateProof, Address, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+
+/// ID of the verified registry actor.
+pub const VERIFIED_REGISTRY_ACTOR_ID: u64 = 6;
+
+/// Position of the allocations HAMT, keyed by client, in the verified registry state.
+const ALLOCATIONS_FIELD: usize = 3;
+/// Position of the claims HAMT, keyed by provider, in the verified registry state.
+const CLAIMS_FIELD: usize = 5;
+
+/// Allocation of DataCap by a client to a provider, for a piece of data.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct Allocation {
+    pub client: u64,
+    pub provider: u64,
+    /// Piece Cid of the data.
+    pub data: Cid,
+    /// Padded size of the data.
+    pub size: u64,
+    /// Minimum term the provider must commit to storing the data.
+    pub term_min: i64,
+    /// Maximum term the provider can commit to storing the data.
+    pub term_max: i64,
+    /// Epoch after which the allocation can no longer be claimed.
+    pub expiration: i64,
+}
+
+/// Claim of an allocation by a provider, which has committed the data to a sector.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct VerifiedClaim {
+    pub provider: u64,
+    pub client: u64,
+    /// Piece Cid of the data.
+    pub data: Cid,
+    /// Padded size of the data.
+    pub size: u64,
+    pub term_min: i64,
+    pub term_max: i64,
+    /// Epoch the data was committed at.
+    pub term_start: i64,
+    /// Sector the data is committed to.
+    pub sector: u64,
+}
+
+/// Proof of a client's allocation in the verified registry.
+///
+/// The proof chains the verified registry's state in the state tree to a segment through the
+/// allocations HAMT, keyed by client, and the client's HAMT keyed by allocation ID.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AllocationProof {
+    client: Address,
+    allocation_id: u64,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl AllocationProof {
+    /// Verifies the proof against the state root and returns the allocation, or `None` if the
+    /// client has no allocation with the ID.
+    pub fn verify(&self, state_root: &Cid) -> Result<Option<Allocation>> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the allocation, or `None` if the client has no allocation with the ID.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Option<Allocation>> {
+        let value = verify_nested(
+            &self.actor,
+            &self.path,
+            state_root,
+            ALLOCATIONS_FIELD,
+            &self.client,
+            self.allocation_id,
+            hasher,
+        )?;
+        let allocation: Option<Allocation> = value.as_ref().map(decode_value).transpose()?;
+        if let Some(allocation) = &allocation {
+            if Some(allocation.client) != self.client.id() {
+                return Err(
+                    Error::InvalidNode("allocation client does not match key".into()).into(),
+                );
+            }
+        }
+        Ok(allocation)
+    }
+
+    /// Returns the address of the client.
+    pub fn client(&self) -> &Address {
+        &self.client
+    }
+
+    /// Returns the ID of the allocation being proven.
+    pub fn allocation_id(&self) -> u64 {
+        self.allocation_id
+    }
+}
+
+/// Proof of a provider's claim in the verified registry.
+///
+/// The proof chains the verified registry's state in the state tree to a segment through the
+/// claims HAMT, keyed by provider, and the provider's HAMT keyed by claim ID.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct VerifiedClaimProof {
+    provider: Address,
+    claim_id: u64,
+    actor: ActorStateProof,
+    path: Proof,
+}
+
+impl VerifiedClaimProof {
+    /// Verifies the proof against the state root and returns the claim, or `None` if the
+    /// provider has no claim with the ID.
+    pub fn verify(&self, state_root: &Cid) -> Result<Option<VerifiedClaim>> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the hasher,
+    /// and returns the claim, or `None` if the provider has no claim with the ID.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Option<VerifiedClaim>> {
+        let value = verify_nested(
+            &self.actor,
+            &self.path,
+            state_root,
+            CLAIMS_FIELD,
+            &self.provider,
+            self.claim_id,
+            hasher,
+        )?;
+        let claim: Option<VerifiedClaim> = value.as_ref().map(decode_value).transpose()?;
+        if let Some(claim) = &claim {
+            if Some(claim.provider) != self.provider.id() {
+                return Err(Error::InvalidNode("claim provider does not match key".into()).into());
+            }
+        }
+        Ok(claim)
+    }
+
+    /// Returns the address of the provider.
+    pub fn provider(&self) -> &Address {
+        &self.provider
+    }
+
+    /// Returns the ID of the claim being proven.
+    pub fn claim_id(&self) -> u64 {
+        self.claim_id
+    }
+}
+
+/// Verifies the path through the HAMT at the field of the verified registry state, keyed by
+/// actor ID, and the nested HAMT keyed by ID. If the actor has no nested HAMT, the path ends at
+/// the outer HAMT.
+fn verify_nested<H: ProofHasher + ?Sized>(
+    actor: &ActorStateProof,
+    path: &Proof,
+    state_root: &Cid,
+    position: usize,
+    outer: &Address,
+    inner: u64,
+    hasher: &H,
+) -> Result<Option<Ipld>> {
+    let registry = actor.verify_with(state_root, hasher)?;
+    if actor.address().id() != Some(VERIFIED_REGISTRY_ACTOR_ID) {
+        return Err(Error::InvalidNode("actor is not the verified registry actor".into()).into());
+    }
+
+    let mut nodes = path.nodes().iter().rev();
+    let root: Cid = field(
+        &decode_fields(nodes.next(), &registry.head, hasher)?,
+        position,
+    )?;
+    let key = id_key(outer)?;
+    match hamt::verify_path(&mut nodes, &root, &key, hamt::DEFAULT_BIT_WIDTH, hasher)? {
+        Some(value) => {
+            let inner_root: Cid = decode_value(&value)?;
+            hamt::verify(
+                nodes,
+                &inner_root,
+                &u64_key(inner),
+                hamt::DEFAULT_BIT_WIDTH,
+                hasher,
+            )
+        }
+        None if nodes.next().is_some() => Err(Error::UnexpectedNodes.into()),
+        None => Ok(None),
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the client's allocation with the ID. The client must be an ID
+    /// address, and the allocation does not have to exist.
+    pub fn generate_allocation_proof(
+        &self,
+        state_root: &Cid,
+        client: &Address,
+        allocation_id: u64,
+    ) -> Result<AllocationProof> {
+        let (actor, path) =
+            self.generate_nested_proof(state_root, ALLOCATIONS_FIELD, client, allocation_id)?;
+        Ok(AllocationProof {
+            client: client.clone(),
+            allocation_id,
+            actor,
+            path,
+        })
+    }
+
+    /// Generates a proof of the provider's claim with the ID. The provider must be an ID
+    /// address, and the claim does not have to exist.
+    pub fn generate_verified_claim_proof(
+        &self,
+        state_root: &Cid,
+        provider: &Address,
+        claim_id: u64,
+    ) -> Result<VerifiedClaimProof> {
+        let (actor, path) =
+            self.generate_nested_proof(state_root, CLAIMS_FIELD, provider, claim_id)?;
+        Ok(VerifiedClaimProof {
+            provider: provider.clone(),
+            claim_id,
+            actor,
+            path,
+        })
+    }
+
+    fn generate_nested_proof(
+        &self,
+        state_root: &Cid,
+        position: usize,
+        outer: &Address,
+        inner: u64,
+    ) -> Result<(ActorStateProof, Proof)> {
+        let addr = Address::new_id(VERIFIED_REGISTRY_ACTOR_ID);
+        let registry = self
+            .get_actor(state_root, &addr)?
+            .ok_or(Error::KeyNotFound)?;
+
+        // Nested HAMTs are loaded through a new generator, to only connect the nodes along the
+        // path from the registry's state.
+        let path = self.nested();
+        let root: Cid = field(&load_fields(&path, &registry.head)?, position)?;
+        let (value, mut leaf) =
+            hamt::lookup(&path, &root, &id_key(outer)?, hamt::DEFAULT_BIT_WIDTH)?;
+        if let Some(value) = value {
+            let inner_root: Cid = decode_value(&value)?;
+            leaf = hamt::lookup(&path, &inner_root, &u64_key(inner), hamt::DEFAULT_BIT_WIDTH)?.1;
+        }
+        Ok((
+            self.generate_actor_proof(state_root, &addr)?,
+            path.generate_proof_raw(leaf, Some(&registry.head))?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actors::{build_actors, test_actor};
+    use crate::DEFAULT_HASH_CODE;
+    use forest_ipld::to_ipld;
+
+    fn allocation(client: u64, id: u64, data: Cid) -> Allocation {
+        Allocation {
+            client,
+            provider: 2000 + id % 3,
+            data,
+            size: 1 << 35,
+            term_min: 518_400,
+            term_max: 5_256_000,
+            expiration: 100_000 + id as i64,
+        }
+    }
+
+    fn claim(provider: u64, id: u64, data: Cid) -> VerifiedClaim {
+        VerifiedClaim {
+            provider,
+            client: 1000 + id % 7,
+            data,
+            size: 1 << 35,
+            term_min: 518_400,
+            term_max: 5_256_000,
+            term_start: 90_000,
+            sector: id * 4,
+        }
+    }
+
+    /// Builds a HAMT keyed by actor ID of nested HAMTs keyed by ID.
+    fn build_nested<BS: BlockStore>(
+        bs: &BS,
+        outer: std::ops::Range<u64>,
+        inner: std::ops::Range<u64>,
+        value: impl Fn(u64, u64) -> Ipld,
+    ) -> Cid {
+        let entries: Vec<_> = outer
+            .map(|o| {
+                let nested: Vec<_> = inner.clone().map(|i| (u64_key(i), value(o, i))).collect();
+                let root = hamt::build(bs, &nested, hamt::DEFAULT_BIT_WIDTH);
+                (u64_key(o), Ipld::Link(root))
+            })
+            .collect();
+        hamt::build(bs, &entries, hamt::DEFAULT_BIT_WIDTH)
+    }
+
+    #[test]
+    fn allocation_and_claim_proofs() {
+        let bs = forest_db::MemoryDB::default();
+        let data = bs.put(&"piece", DEFAULT_HASH_CODE).unwrap();
+        let allocations = build_nested(&bs, 1000..1020, 0..30, |c, i| {
+            to_ipld(allocation(c, i, data)).unwrap()
+        });
+        let claims = build_nested(&bs, 2000..2005, 0..50, |p, i| {
+            to_ipld(claim(p, i, data)).unwrap()
+        });
+        let empty = hamt::build(&bs, &[], hamt::DEFAULT_BIT_WIDTH);
+        let state = Ipld::List(vec![
+            Ipld::Bytes(Address::new_id(80).to_bytes()),
+            Ipld::Link(empty),
+            Ipld::Link(empty),
+            Ipld::Link(allocations),
+            Ipld::Integer(30),
+            Ipld::Link(claims),
+        ]);
+        let head = bs.put(&state, DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_actors(
+            &bs,
+            &[(VERIFIED_REGISTRY_ACTOR_ID, test_actor(empty, head))],
+        );
+
+        let p_gen = ProofGenerator::new(&bs);
+        let client = Address::new_id(1007);
+        let proof = p_gen
+            .generate_allocation_proof(&state_root, &client, 12)
+            .unwrap();
+        assert_eq!(
+            proof.verify(&state_root).unwrap(),
+            Some(allocation(1007, 12, data))
+        );
+
+        let provider = Address::new_id(2003);
+        let proof = p_gen
+            .generate_verified_claim_proof(&state_root, &provider, 49)
+            .unwrap();
+        assert_eq!(
+            proof.verify(&state_root).unwrap(),
+            Some(claim(2003, 49, data))
+        );
+
+        // Missing IDs and actors without a nested HAMT are proven absent.
+        for (actor, id) in &[(1007, 30), (1500, 0)] {
+            let proof = p_gen
+                .generate_allocation_proof(&state_root, &Address::new_id(*actor), *id)
+                .unwrap();
+            assert_eq!(proof.verify(&state_root).unwrap(), None);
+        }
+
+        // Path through the claims can't prove an allocation.
+        let proof = p_gen
+            .generate_verified_claim_proof(&state_root, &Address::new_id(2001), 3)
+            .unwrap();
+        let forged = AllocationProof {
+            client: Address::new_id(2001),
+            allocation_id: 3,
+            actor: proof.actor,
+            path: proof.path,
+        };
+        assert!(forged.verify(&state_root).is_err());
+    }
+}
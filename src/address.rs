@@ -0,0 +1,151 @@
+use crate::Error;
+use anyhow::Result;
+use forest_encoding::serde_bytes;
+use integer_encoding::VarInt;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Length of the payload for secp256k1 and actor addresses.
+const PAYLOAD_HASH_LEN: usize = 20;
+/// Length of a BLS public key.
+const BLS_PUB_LEN: usize = 48;
+/// Maximum length of the subaddress of a delegated address.
+const MAX_SUBADDRESS_LEN: usize = 54;
+
+/// Protocol of an [Address], which determines how the payload is interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protocol {
+    Id = 0,
+    Secp256k1 = 1,
+    Actor = 2,
+    Bls = 3,
+    Delegated = 4,
+}
+
+/// Filecoin address. Only the byte representation is supported, as this is what is used for
+/// keys within the state tree and actor states.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Address {
+    protocol: Protocol,
+    payload: Vec<u8>,
+}
+
+impl Address {
+    /// Creates a new ID address.
+    pub fn new_id(id: u64) -> Self {
+        Self {
+            protocol: Protocol::Id,
+            payload: id.encode_var_vec(),
+        }
+    }
+
+    /// Decodes an address from its byte representation.
+    pub fn from_bytes(bz: &[u8]) -> Result<Self> {
+        let (&first, payload) = bz
+            .split_first()
+            .ok_or(Error::InvalidAddress("empty address"))?;
+        let protocol = match first {
+            0 => {
+                match u64::decode_var(payload) {
+                    Some((_, len)) if len == payload.len() => (),
+                    _ => return Err(Error::InvalidAddress("invalid id payload").into()),
+                }
+                Protocol::Id
+            }
+            1 | 2 => {
+                if payload.len() != PAYLOAD_HASH_LEN {
+                    return Err(Error::InvalidAddress("invalid payload length").into());
+                }
+                if first == 1 {
+                    Protocol::Secp256k1
+                } else {
+                    Protocol::Actor
+                }
+            }
+            3 => {
+                if payload.len() != BLS_PUB_LEN {
+                    return Err(Error::InvalidAddress("invalid payload length").into());
+                }
+                Protocol::Bls
+            }
+            4 => {
+                match u64::decode_var(payload) {
+                    Some((_, len)) if payload.len() - len <= MAX_SUBADDRESS_LEN => (),
+                    _ => return Err(Error::InvalidAddress("invalid delegated payload").into()),
+                }
+                Protocol::Delegated
+            }
+            _ => return Err(Error::InvalidAddress("unknown protocol").into()),
+        };
+        Ok(Self {
+            protocol,
+            payload: payload.to_vec(),
+        })
+    }
+
+    /// Returns the byte representation of the address.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bz = Vec::with_capacity(self.payload.len() + 1);
+        bz.push(self.protocol as u8);
+        bz.extend_from_slice(&self.payload);
+        bz
+    }
+
+    /// Returns the [Protocol] of the address.
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
+    }
+
+    /// Returns the actor ID if the address is an ID address.
+    pub fn id(&self) -> Option<u64> {
+        match self.protocol {
+            Protocol::Id => u64::decode_var(&self.payload).map(|(id, _)| id),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serde_bytes::Bytes::new(&self.to_bytes()).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let bz = serde_bytes::ByteBuf::deserialize(deserializer)?;
+        Address::from_bytes(&bz).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_address_bytes() {
+        let addr = Address::new_id(1024);
+        assert_eq!(addr.to_bytes(), vec![0, 0x80, 0x08]);
+        assert_eq!(addr.id(), Some(1024));
+
+        let decoded = Address::from_bytes(&addr.to_bytes()).unwrap();
+        assert_eq!(decoded, addr);
+        assert_eq!(
+            serde_cbor::from_slice::<Address>(&serde_cbor::to_vec(&addr).unwrap()).unwrap(),
+            addr
+        );
+    }
+
+    #[test]
+    fn invalid_addresses() {
+        assert!(Address::from_bytes(&[]).is_err());
+        assert!(Address::from_bytes(&[1, 2, 3]).is_err());
+        assert!(Address::from_bytes(&[0, 0x80]).is_err());
+        assert!(Address::from_bytes(&[9, 1]).is_err());
+    }
+}
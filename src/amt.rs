@@ -0,0 +1,381 @@
+//! Minimal read-only support for the Filecoin AMT, used to generate and verify proofs of values
+//! at an index of an array.
+//!
+//! The root is encoded as `[bit_width, height, count, node]`, or `[height, count, node]` with a
+//! bit width of 3 for the legacy format still used for message and receipt arrays in the chain.
+//! Nodes are encoded as `[bitmap, links, values]`, and only the root node is inlined.
+
+use crate::{check_cid_with, load_bytes, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher};
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use forest_encoding::{de::DeserializeOwned, tuple::*, BytesDe};
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+
+/// Bit width of the legacy AMT format, which does not include it in the root.
+const LEGACY_BIT_WIDTH: u32 = 3;
+
+/// Maximum height of an AMT, which bounds the index to a u64.
+const MAX_HEIGHT: u64 = 64;
+
+#[derive(Deserialize_tuple)]
+struct Node {
+    bitmap: BytesDe,
+    links: Vec<Cid>,
+    values: Vec<Ipld>,
+}
+
+/// Outcome of searching for an index within a single node.
+enum Lookup {
+    /// Index can only exist within the linked child node.
+    Link(Cid),
+    /// Value at the index.
+    Found(Ipld),
+    /// Index does not exist within the AMT.
+    Absent,
+}
+
+/// Position of the search within the AMT, starting at the root.
+struct Cursor {
+    bit_width: u32,
+    height: u64,
+    index: u64,
+}
+
+impl Cursor {
+    /// Decodes the root node, returning the cursor and the root node.
+    fn from_root(bytes: &[u8], index: u64) -> Result<(Self, Node)> {
+        let root: Vec<Ipld> = forest_encoding::from_slice(bytes)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode AMT root: {}", e)))?;
+        let (bit_width, height, node) = match root.as_slice() {
+            [Ipld::Integer(bw), Ipld::Integer(h), Ipld::Integer(_), node] => (*bw, *h, node),
+            [Ipld::Integer(h), Ipld::Integer(_), node] => (LEGACY_BIT_WIDTH as i128, *h, node),
+            _ => return Err(Error::InvalidNode("invalid AMT root".into()).into()),
+        };
+        if !(1..=16).contains(&bit_width) || !(0..=MAX_HEIGHT as i128).contains(&height) {
+            return Err(Error::InvalidNode("invalid AMT root".into()).into());
+        }
+        let node = forest_ipld::from_ipld(node).map_err(|e| anyhow!(e))?;
+        let cursor = Self {
+            bit_width: bit_width as u32,
+            height: height as u64,
+            index,
+        };
+        Ok((cursor, node))
+    }
+
+    fn search(&mut self, node: Node) -> Result<Lookup> {
+        // Each slot of the node covers 2^shift indexes.
+        let shift = self.height * self.bit_width as u64;
+        let slot = if shift < 64 { self.index >> shift } else { 0 };
+        if slot >= 1 << self.bit_width {
+            return Ok(Lookup::Absent);
+        }
+
+        let slot = slot as usize;
+        let bitmap = &node.bitmap.0;
+        if !bit_set(bitmap, slot) {
+            return Ok(Lookup::Absent);
+        }
+        let position = (0..slot).filter(|i| bit_set(bitmap, *i)).count();
+
+        if self.height == 0 {
+            return node
+                .values
+                .into_iter()
+                .nth(position)
+                .map(Lookup::Found)
+                .ok_or_else(|| Error::InvalidNode("invalid AMT values".into()).into());
+        }
+        let link = node
+            .links
+            .get(position)
+            .ok_or_else(|| Error::InvalidNode("invalid AMT links".into()))?;
+        if shift < 64 {
+            self.index &= (1 << shift) - 1;
+        }
+        self.height -= 1;
+        Ok(Lookup::Link(*link))
+    }
+}
+
+/// Checks the bit at the index of the bitmap, where the first bit is the least significant bit of
+/// the first byte.
+fn bit_set(bitmap: &[u8], i: usize) -> bool {
+    i / 8 < bitmap.len() && bitmap[i / 8] & (1 << (i % 8)) != 0
+}
+
+fn decode_node(bytes: &[u8]) -> Result<Node> {
+    Ok(forest_encoding::from_slice(bytes)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode AMT node: {}", e)))?)
+}
+
+/// Looks up the index in the AMT with root `root`, loading all nodes through the store. Returns
+/// the value if it exists, along with the bytes of the last node searched.
+pub(crate) fn lookup<BS: BlockStore>(
+    bs: &BS,
+    root: &Cid,
+    index: u64,
+) -> Result<(Option<Ipld>, Vec<u8>)> {
+    let mut bytes = load_bytes(bs, root)?;
+    let (mut cursor, mut node) = Cursor::from_root(&bytes, index)?;
+    loop {
+        match cursor.search(node)? {
+            Lookup::Link(child) => {
+                bytes = load_bytes(bs, &child)?;
+                node = decode_node(&bytes)?;
+            }
+            Lookup::Found(value) => return Ok((Some(value), bytes)),
+            Lookup::Absent => return Ok((None, bytes)),
+        }
+    }
+}
+
+/// Verifies that the nodes, ordered from the root, are the path to the index in the AMT with
+/// root `root`. Returns the value at the index, or `None` if the nodes prove it does not exist.
+pub(crate) fn verify<'a, I, H>(nodes: I, root: &Cid, index: u64, hasher: &H) -> Result<Option<Ipld>>
+where
+    I: IntoIterator<Item = &'a Vec<u8>>,
+    H: ProofHasher + ?Sized,
+{
+    let mut nodes = nodes.into_iter();
+    let root_node = nodes.next().ok_or(Error::IncompleteProof)?;
+    check_cid_with(root_node, root, hasher)?;
+    let (mut cursor, mut node) = Cursor::from_root(root_node, index)?;
+    let value = loop {
+        match cursor.search(node)? {
+            Lookup::Link(child) => {
+                let bytes = nodes.next().ok_or(Error::IncompleteProof)?;
+                check_cid_with(bytes, &child, hasher)?;
+                node = decode_node(bytes)?;
+            }
+            Lookup::Found(value) => break Some(value),
+            Lookup::Absent => break None,
+        }
+    };
+    if nodes.next().is_some() {
+        return Err(Error::UnexpectedNodes.into());
+    }
+    Ok(value)
+}
+
+/// Iterates over all values in the AMT, loading all nodes through the store.
+pub(crate) fn for_each<BS, F>(bs: &BS, root: &Cid, mut f: F) -> Result<()>
+where
+    BS: BlockStore,
+    F: FnMut(u64, &Ipld) -> Result<()>,
+{
+    fn for_each_node<BS, F>(
+        bs: &BS,
+        node: Node,
+        height: u64,
+        bit_width: u32,
+        offset: u64,
+        f: &mut F,
+    ) -> Result<()>
+    where
+        BS: BlockStore,
+        F: FnMut(u64, &Ipld) -> Result<()>,
+    {
+        let shift = (height * bit_width as u64) as u32;
+        let set_slots = (0..1usize << bit_width).filter(|i| bit_set(&node.bitmap.0, *i));
+        if height == 0 {
+            for (slot, value) in set_slots.zip(&node.values) {
+                f(offset + slot as u64, value)?;
+            }
+        } else {
+            for (slot, link) in set_slots.zip(&node.links) {
+                let child = decode_node(&load_bytes(bs, link)?)?;
+                let offset = match slot {
+                    0 => offset,
+                    _ => (slot as u64)
+                        .checked_shl(shift)
+                        .filter(|o| o >> shift == slot as u64)
+                        .and_then(|o| o.checked_add(offset))
+                        .ok_or_else(|| Error::InvalidNode("AMT index overflow".into()))?,
+                };
+                for_each_node(bs, child, height - 1, bit_width, offset, f)?;
+            }
+        }
+        Ok(())
+    }
+
+    let (cursor, node) = Cursor::from_root(&load_bytes(bs, root)?, 0)?;
+    for_each_node(bs, node, cursor.height, cursor.bit_width, 0, &mut f)
+}
+
+/// Proof of the value at an index of an AMT.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AmtProof {
+    index: u64,
+    proof: Proof,
+}
+
+impl AmtProof {
+    /// Verifies the proof against the root of the AMT, and returns the value at the index.
+    pub fn verify<T: DeserializeOwned>(&self, root: &Cid) -> Result<T> {
+        self.verify_with(root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the root of the AMT, computing the [Cid] of each node with
+    /// the hasher, and returns the value at the index.
+    pub fn verify_with<T, H>(&self, root: &Cid, hasher: &H) -> Result<T>
+    where
+        T: DeserializeOwned,
+        H: ProofHasher + ?Sized,
+    {
+        let value = verify(self.proof.nodes().iter().rev(), root, self.index, hasher)?
+            .ok_or(Error::KeyNotFound)?;
+        forest_ipld::from_ipld(&value).map_err(|e| anyhow!(e))
+    }
+
+    /// Returns the index of the value being proven.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the value at the index of the AMT with the root.
+    pub fn generate_amt_proof(&self, root: &Cid, index: u64) -> Result<AmtProof> {
+        let (value, leaf) = lookup(self, root, index)?;
+        if value.is_none() {
+            return Err(Error::KeyNotFound.into());
+        }
+        Ok(AmtProof {
+            index,
+            proof: self.generate_proof_raw(leaf, Some(root))?,
+        })
+    }
+}
+
+/// Builds an AMT from the values, which are placed at the index of their position. `Null` values
+/// are left out, to build sparse arrays. The legacy format is used if no bit width is provided.
+#[cfg(test)]
+pub(crate) fn build<BS: BlockStore>(bs: &BS, values: &[Ipld], bit_width: Option<u32>) -> Cid {
+    use crate::DEFAULT_HASH_CODE;
+
+    fn build_node<BS: BlockStore>(bs: &BS, values: &[Ipld], height: u64, bw: u32) -> Ipld {
+        let width = 1usize << bw;
+        let slot_size = 1usize << (height as u32 * bw);
+        let mut bitmap = vec![0u8; usize::max(width / 8, 1)];
+        let (mut links, mut leaves) = (Vec::new(), Vec::new());
+        for (slot, chunk) in values.chunks(slot_size).enumerate().take(width) {
+            if chunk.iter().all(|v| *v == Ipld::Null) {
+                continue;
+            }
+            bitmap[slot / 8] |= 1 << (slot % 8);
+            if height == 0 {
+                leaves.push(chunk[0].clone());
+            } else {
+                let child = build_node(bs, chunk, height - 1, bw);
+                links.push(Ipld::Link(bs.put(&child, DEFAULT_HASH_CODE).unwrap()));
+            }
+        }
+        Ipld::List(vec![
+            Ipld::Bytes(bitmap),
+            Ipld::List(links),
+            Ipld::List(leaves),
+        ])
+    }
+
+    let bw = bit_width.unwrap_or(LEGACY_BIT_WIDTH);
+    let mut height = 0;
+    while values.len() > 1 << ((height + 1) * bw) {
+        height += 1;
+    }
+    let node = build_node(bs, values, height as u64, bw);
+    let count = values.iter().filter(|v| **v != Ipld::Null).count();
+    let count = Ipld::Integer(count as i128);
+    let root = match bit_width {
+        Some(bw) => vec![
+            Ipld::Integer(bw as i128),
+            Ipld::Integer(height as i128),
+            count,
+            node,
+        ],
+        None => vec![Ipld::Integer(height as i128), count, node],
+    };
+    bs.put(&Ipld::List(root), DEFAULT_HASH_CODE).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values(n: usize) -> Vec<Ipld> {
+        (0..n)
+            .map(|i| Ipld::String(format!("value {}", i)))
+            .collect()
+    }
+
+    #[test]
+    fn index_proofs() {
+        for bit_width in &[None, Some(5), Some(3)] {
+            let bs = forest_db::MemoryDB::default();
+            let root = build(&bs, &values(700), *bit_width);
+
+            for i in &[0u64, 8, 63, 64, 699] {
+                let p_gen = ProofGenerator::new(&bs);
+                let proof = p_gen.generate_amt_proof(&root, *i).unwrap();
+                assert_eq!(
+                    proof.verify::<String>(&root).unwrap(),
+                    format!("value {}", i)
+                );
+                assert!(proof.proof().nodes().len() > 1);
+            }
+
+            let p_gen = ProofGenerator::new(&bs);
+            assert!(p_gen.generate_amt_proof(&root, 700).is_err());
+            let (value, leaf) = lookup(&p_gen, &root, 100_000).unwrap();
+            assert_eq!(value, None);
+            let proof = p_gen.generate_proof_raw(leaf, Some(&root)).unwrap();
+            assert_eq!(
+                verify(proof.nodes().iter().rev(), &root, 100_000, p_gen.hasher()).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn iterate_values() {
+        let bs = forest_db::MemoryDB::default();
+        let root = build(&bs, &values(100), Some(3));
+        let mut found = Vec::new();
+        for_each(&bs, &root, |i, v| {
+            found.push((i, v.clone()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            found,
+            values(100)
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (i as u64, v))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn wrong_index_rejected() {
+        let bs = forest_db::MemoryDB::default();
+        let root = build(&bs, &values(100), None);
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_amt_proof(&root, 3).unwrap();
+        let forged = AmtProof {
+            index: 90,
+            proof: proof.proof,
+        };
+        assert!(forged.verify::<String>(&root).is_err());
+    }
+}
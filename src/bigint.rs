@@ -0,0 +1,76 @@
+//! Serialization of Filecoin big integers, which are encoded as bytes with a leading sign byte
+//! followed by the big-endian magnitude. Zero is encoded as empty bytes.
+//!
+//! Values are decoded into an `i128`, which fits all token amounts and storage power values.
+
+use forest_encoding::serde_bytes;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Amount of attoFIL.
+pub type TokenAmount = i128;
+
+/// Amount of storage power, in bytes.
+pub type StoragePower = i128;
+
+pub(crate) fn serialize<S>(int: &i128, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let mut bz = Vec::with_capacity(17);
+    if *int != 0 {
+        bz.push(if *int < 0 { 1 } else { 0 });
+        let magnitude = int.unsigned_abs().to_be_bytes();
+        let start = magnitude.iter().position(|b| *b != 0).unwrap_or(0);
+        bz.extend_from_slice(&magnitude[start..]);
+    }
+    serde_bytes::Bytes::new(&bz).serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<i128, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let bz = serde_bytes::ByteBuf::deserialize(deserializer)?;
+    let (sign, magnitude) = match bz.split_first() {
+        Some(split) => split,
+        None => return Ok(0),
+    };
+    if magnitude.len() > 16 {
+        return Err(de::Error::custom("big integer exceeds 128 bits"));
+    }
+    let mut buf = [0u8; 16];
+    buf[16 - magnitude.len()..].copy_from_slice(magnitude);
+    let value = u128::from_be_bytes(buf);
+    match sign {
+        0 if value <= i128::MAX as u128 => Ok(value as i128),
+        1 if value <= i128::MAX as u128 + 1 => Ok((value as i128).wrapping_neg()),
+        0 | 1 => Err(de::Error::custom("big integer exceeds 128 bits")),
+        _ => Err(de::Error::custom("invalid big integer sign byte")),
+    }
+}
+
+/// Wrapper to serialize a big integer outside of a struct field, such as an element of a
+/// manually decoded sequence.
+#[derive(Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct BigInt(#[serde(with = "self")] pub i128);
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(transparent)]
+    struct Int(#[serde(with = "super")] i128);
+
+    #[test]
+    fn bigint_symmetric() {
+        for v in &[0, 1, -1, 256, -12_345_678_901_234, i128::MAX, i128::MIN] {
+            let bz = serde_cbor::to_vec(&Int(*v)).unwrap();
+            assert_eq!(serde_cbor::from_slice::<Int>(&bz).unwrap(), Int(*v));
+        }
+        // Zero is empty bytes, 256 is positive sign followed by magnitude.
+        assert_eq!(serde_cbor::to_vec(&Int(0)).unwrap(), vec![0x40]);
+        assert_eq!(serde_cbor::to_vec(&Int(256)).unwrap(), vec![0x43, 0, 1, 0]);
+    }
+}
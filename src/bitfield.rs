@@ -0,0 +1,371 @@
+//! Decoding of RLE+ bitfields, which Filecoin uses to store sets of sector numbers.
+//!
+//! The encoding starts with a 2 bit version, which must be 0, and the value of the first run.
+//! Runs then alternate between unset and set bits, each length encoded as either a single `1`
+//! bit for a length of 1, `01` followed by a 4 bit length, or `00` followed by a varint. Bits are
+//! read starting with the least significant bit of each byte.
+
+use crate::{load_bytes, Blake2bHasher, Error, Proof, ProofGenerator, ProofHasher, StateProof};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::tuple::*;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use std::convert::TryFrom;
+use std::ops::Range;
+
+/// Bitfield decoded into the ranges of set bits.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BitField {
+    runs: Vec<Range<u64>>,
+}
+
+impl BitField {
+    /// Decodes the RLE+ encoded bytes, rejecting encodings which are not minimal.
+    pub fn from_bytes(bz: &[u8]) -> Result<Self> {
+        match bz.last() {
+            None => return Ok(Self::default()),
+            Some(0) => return Err(invalid("trailing zeros")),
+            Some(_) => (),
+        }
+
+        let mut reader = BitReader { bz, pos: 0 };
+        if reader.read(2)? != 0 {
+            return Err(invalid("unsupported version"));
+        }
+        let mut set = reader.read(1)? == 1;
+        let mut runs = Vec::new();
+        let mut next = 0u64;
+        while reader.has_more() {
+            let len = reader.read_len()?;
+            let end = next
+                .checked_add(len)
+                .ok_or_else(|| invalid("bitfield overflows"))?;
+            if set {
+                runs.push(next..end);
+            }
+            next = end;
+            set = !set;
+        }
+        Ok(Self { runs })
+    }
+
+    /// Checks if the bit is set.
+    pub fn get(&self, bit: u64) -> bool {
+        self.runs.iter().any(|run| run.contains(&bit))
+    }
+
+    /// Returns the ranges of set bits, in increasing order.
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.runs
+    }
+
+    /// Returns an iterator over the set bits, in increasing order.
+    pub fn iter(&self) -> impl Iterator<Item = u64> + '_ {
+        self.runs.iter().cloned().flatten()
+    }
+
+    /// Returns the number of set bits.
+    pub fn len(&self) -> u64 {
+        self.runs.iter().map(|run| run.end - run.start).sum()
+    }
+
+    /// Returns true if no bits are set.
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+}
+
+/// Proof that a bit is set or unset in an RLE+ bitfield within the leaf node of a proof.
+///
+/// The bitfield is located by its field path in the leaf node, where each element is the
+/// position within a list. For example, a partition's faults in the values of an AMT node are
+/// at `[2, position, 2]`.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct BitFieldProof {
+    field: Vec<u64>,
+    bit: u64,
+    proof: Proof,
+}
+
+impl BitFieldProof {
+    /// Verifies the proof against the root and returns whether the bit is set.
+    pub fn verify(&self, root: &Cid) -> Result<bool> {
+        self.verify_with(root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the root, computing the [Cid] of each node with the hasher,
+    /// and returns whether the bit is set.
+    pub fn verify_with<H: ProofHasher + ?Sized>(&self, root: &Cid, hasher: &H) -> Result<bool> {
+        self.proof.verify_state_with(root, hasher)?;
+        Ok(self.bitfield()?.get(self.bit))
+    }
+
+    /// Decodes the bitfield from the leaf node. This does not verify the proof.
+    pub fn bitfield(&self) -> Result<BitField> {
+        let leaf = self.proof.nodes().first().ok_or(Error::IncompleteProof)?;
+        decode_field(leaf, &self.field)
+    }
+
+    /// Returns the field path of the bitfield within the leaf node.
+    pub fn field(&self) -> &[u64] {
+        &self.field
+    }
+
+    /// Returns the bit being proven.
+    pub fn bit(&self) -> u64 {
+        self.bit
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+/// Decodes the bitfield at the field path of the node.
+fn decode_field(node: &[u8], path: &[u64]) -> Result<BitField> {
+    let mut value: Ipld = forest_encoding::from_slice(node)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode leaf node: {}", e)))?;
+    for position in path {
+        let idx = usize::try_from(*position).ok();
+        value = match (value, idx) {
+            (Ipld::List(mut list), Some(idx)) if idx < list.len() => list.swap_remove(idx),
+            _ => return Err(Error::InvalidNode("bitfield field path not found".into()).into()),
+        };
+    }
+    match value {
+        Ipld::Bytes(bz) => BitField::from_bytes(&bz),
+        _ => Err(Error::InvalidNode("bitfield field is not bytes".into()).into()),
+    }
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Generates a proof of the bit in the bitfield at the field path of the leaf node, up to
+    /// the root. The leaf must have been loaded through the proof generator from the root.
+    pub fn generate_bitfield_proof(
+        &self,
+        leaf: &Cid,
+        root: &Cid,
+        field: Vec<u64>,
+        bit: u64,
+    ) -> Result<BitFieldProof> {
+        let bytes = load_bytes(self, leaf)?;
+        decode_field(&bytes, &field)?;
+        Ok(BitFieldProof {
+            field,
+            bit,
+            proof: self.generate_proof_raw(bytes, Some(root))?,
+        })
+    }
+}
+
+fn invalid(reason: &str) -> anyhow::Error {
+    Error::InvalidNode(format!("invalid RLE+ bitfield: {}", reason)).into()
+}
+
+struct BitReader<'a> {
+    bz: &'a [u8],
+    pos: usize,
+}
+
+impl BitReader<'_> {
+    /// Reads up to 8 bits as a little-endian number.
+    fn read(&mut self, n: usize) -> Result<u8> {
+        let mut out = 0;
+        for i in 0..n {
+            let byte = self
+                .bz
+                .get(self.pos / 8)
+                .ok_or_else(|| invalid("unexpected end"))?;
+            out |= ((byte >> (self.pos % 8)) & 1) << i;
+            self.pos += 1;
+        }
+        Ok(out)
+    }
+
+    /// Checks if any set bits remain, as the last byte is padded with zeros.
+    fn has_more(&self) -> bool {
+        let byte = self.pos / 8;
+        byte < self.bz.len() && (byte + 1 < self.bz.len() || self.bz[byte] >> (self.pos % 8) != 0)
+    }
+
+    fn read_len(&mut self) -> Result<u64> {
+        if self.read(1)? == 1 {
+            return Ok(1);
+        }
+        if self.read(1)? == 1 {
+            return match self.read(4)? {
+                0 | 1 => Err(invalid("non-minimal short run")),
+                len => Ok(len as u64),
+            };
+        }
+
+        let mut len = 0u64;
+        for i in 0..10 {
+            let byte = self.read(8)?;
+            if i == 9 && byte > 1 {
+                return Err(invalid("run length overflows"));
+            }
+            len |= ((byte & 0x7f) as u64) << (7 * i);
+            if byte & 0x80 == 0 {
+                if i > 0 && byte == 0 {
+                    return Err(invalid("non-minimal varint"));
+                }
+                if len < 16 {
+                    return Err(invalid("non-minimal long run"));
+                }
+                return Ok(len);
+            }
+        }
+        Err(invalid("run length overflows"))
+    }
+}
+
+/// Encodes the set bits, which must be sorted and unique, as an RLE+ bitfield.
+#[cfg(test)]
+pub(crate) fn encode(bits: &[u64]) -> Vec<u8> {
+    struct BitWriter {
+        bz: Vec<u8>,
+        pos: usize,
+    }
+    impl BitWriter {
+        fn write(&mut self, value: u64, n: usize) {
+            for i in 0..n {
+                if self.pos / 8 == self.bz.len() {
+                    self.bz.push(0);
+                }
+                *self.bz.last_mut().unwrap() |= (((value >> i) & 1) as u8) << (self.pos % 8);
+                self.pos += 1;
+            }
+        }
+        fn write_len(&mut self, mut len: u64) {
+            match len {
+                1 => self.write(1, 1),
+                2..=15 => {
+                    self.write(0b10, 2);
+                    self.write(len, 4);
+                }
+                _ => {
+                    self.write(0, 2);
+                    while len >= 0x80 {
+                        self.write(len & 0x7f | 0x80, 8);
+                        len >>= 7;
+                    }
+                    self.write(len, 8);
+                }
+            }
+        }
+    }
+
+    if bits.is_empty() {
+        return Vec::new();
+    }
+    let mut runs: Vec<Range<u64>> = Vec::new();
+    for bit in bits {
+        match runs.last_mut() {
+            Some(run) if run.end == *bit => run.end += 1,
+            _ => runs.push(*bit..*bit + 1),
+        }
+    }
+    let mut writer = BitWriter {
+        bz: Vec::new(),
+        pos: 0,
+    };
+    writer.write(0, 2);
+    writer.write((runs[0].start == 0) as u64, 1);
+    let mut next = 0;
+    for run in runs {
+        if run.start > next {
+            writer.write_len(run.start - next);
+        }
+        writer.write_len(run.end - run.start);
+        next = run.end;
+    }
+    writer.bz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_runs() {
+        // Version 0, starting with a set bit, runs of 1 set, 1 unset, 1 set.
+        let bf = BitField::from_bytes(&[0b0011_1100]).unwrap();
+        assert_eq!(bf.runs, vec![0..1, 2..3]);
+        assert!(BitField::from_bytes(&[]).unwrap().runs.is_empty());
+
+        for bits in &[
+            vec![0, 1, 2, 5],
+            vec![3, 20, 21, 22, 1000],
+            (100..200).chain(5000..5001).collect(),
+        ] {
+            let bf = BitField::from_bytes(&encode(bits)).unwrap();
+            for bit in 0..6000 {
+                assert_eq!(bf.get(bit), bits.contains(&bit));
+            }
+        }
+    }
+
+    #[test]
+    fn bitfield_proof() {
+        use crate::DEFAULT_HASH_CODE;
+
+        let bs = forest_db::MemoryDB::default();
+        let leaf = Ipld::List(vec![
+            Ipld::Integer(1),
+            Ipld::List(vec![Ipld::Bytes(encode(&[3, 4, 10])), Ipld::Integer(2)]),
+        ]);
+        let leaf = bs.put(&leaf, DEFAULT_HASH_CODE).unwrap();
+        let root = bs.put(&(leaf, 5u8), DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let (leaf, _): (Cid, u8) = p_gen.get(&root).unwrap().unwrap();
+        let _: Ipld = p_gen.get(&leaf).unwrap().unwrap();
+        let set = p_gen
+            .generate_bitfield_proof(&leaf, &root, vec![1, 0], 4)
+            .unwrap();
+        assert!(set.verify(&root).unwrap());
+        assert_eq!(
+            set.bitfield().unwrap().iter().collect::<Vec<_>>(),
+            [3, 4, 10]
+        );
+        let unset = p_gen
+            .generate_bitfield_proof(&leaf, &root, vec![1, 0], 5)
+            .unwrap();
+        assert!(!unset.verify(&root).unwrap());
+        assert!(unset.verify(&leaf).is_err());
+
+        let bz = serde_cbor::to_vec(&set).unwrap();
+        assert_eq!(serde_cbor::from_slice::<BitFieldProof>(&bz).unwrap(), set);
+
+        // Fields which are not bitfields can't be proven.
+        assert!(p_gen
+            .generate_bitfield_proof(&leaf, &root, vec![1, 1], 0)
+            .is_err());
+        assert!(p_gen
+            .generate_bitfield_proof(&leaf, &root, vec![2], 0)
+            .is_err());
+    }
+
+    #[test]
+    fn reject_invalid() {
+        // Non-zero version
+        assert!(BitField::from_bytes(&[0b0000_0101]).is_err());
+        // Trailing zero byte
+        let mut bz = encode(&[1, 2]);
+        bz.push(0);
+        assert!(BitField::from_bytes(&bz).is_err());
+        // Short run of length 1, which must use the single bit encoding
+        assert!(BitField::from_bytes(&[0b0011_0100, 0b0000_0010]).is_err());
+        // Long run of length 3, which must use the short encoding
+        assert!(BitField::from_bytes(&[0b0110_0100, 0b0010_0000]).is_err());
+        // Truncated varint
+        assert!(BitField::from_bytes(&[0b0000_0100, 0b0001_0000]).is_err());
+    }
+}
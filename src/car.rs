@@ -0,0 +1,343 @@
+//! Export and import of proofs as CAR (content addressable archive) files, which can be read
+//! and written by other IPLD tooling.
+
+use crate::{check_cid, link_scanner::LinkScanner, Error, Proof, DEFAULT_HASH_CODE};
+use anyhow::Result;
+use byteorder::{ByteOrder, LittleEndian};
+use cid::Cid;
+use integer_encoding::VarInt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, Read, Write};
+
+/// CARv2 pragma, which is the section of a CARv1 header with only a version of 2.
+const CARV2_PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+/// Length of the CARv2 header which follows the pragma.
+const CARV2_HEADER_LEN: usize = 40;
+
+/// Header of a CARv1 file. Fields are ordered as their keys are sorted in DAG-CBOR.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CarHeader {
+    #[serde(default)]
+    pub roots: Vec<Cid>,
+    pub version: u64,
+}
+
+impl Proof {
+    /// Encodes the proof as a CARv1 with the proof root as the only root. Blocks are written
+    /// from the root to the leaf.
+    pub fn to_car(&self) -> Vec<u8> {
+        let mut car = Vec::new();
+        self.write_car(&mut car)
+            .expect("writing to a vector cannot fail");
+        car
+    }
+
+    /// Writes the proof as a CARv1 with the proof root as the only root. Blocks are written
+    /// from the root to the leaf.
+    pub fn write_car<W: Write>(&self, mut writer: W) -> Result<()> {
+        let header = forest_encoding::to_vec(&CarHeader {
+            roots: vec![self.root()],
+            version: 1,
+        })?;
+        write_section(&mut writer, &[&header])?;
+
+        let mut written = HashSet::new();
+        for node in self.nodes.iter().rev() {
+            let cid = cid::new_from_cbor(node, DEFAULT_HASH_CODE);
+            if written.insert(cid) {
+                write_section(&mut writer, &[&cid.to_bytes(), node])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads a CARv1 or CARv2 and reconstructs the proof of the `leaf` block from a root of the
+    /// CAR. Every block is checked against its Cid, and the path is the shortest chain of links
+    /// from a root to the leaf through the blocks of the CAR.
+    pub fn from_car<R: Read>(mut reader: R, leaf: &Cid) -> Result<Self> {
+        let (len, len_size) = read_varint(&mut reader)?.ok_or_else(|| invalid("missing header"))?;
+        let section = read_exact(&mut reader, len)?;
+        // CARv2 starts with the exact pragma bytes, rather than any header with a version of 2.
+        if len_size == 1 && section[..] == CARV2_PRAGMA[1..] {
+            let mut v2_header = [0u8; CARV2_HEADER_LEN];
+            reader.read_exact(&mut v2_header)?;
+            let data_offset = LittleEndian::read_u64(&v2_header[16..24]);
+            let data_size = LittleEndian::read_u64(&v2_header[24..32]);
+            let skip = data_offset
+                .checked_sub((CARV2_PRAGMA.len() + CARV2_HEADER_LEN) as u64)
+                .ok_or_else(|| invalid("CARv2 data offset overlaps the header"))?;
+            io::copy(&mut (&mut reader).take(skip), &mut io::sink())?;
+
+            let mut data = reader.take(data_size);
+            let header = read_header(&mut data)?;
+            if header.version != 1 {
+                return Err(invalid("CARv2 data payload is not a CARv1"));
+            }
+            return read_blocks(data, header.roots, leaf);
+        }
+
+        let header = decode_header(&section)?;
+        match header.version {
+            1 => read_blocks(reader, header.roots, leaf),
+            2 => Err(invalid("CARv2 does not start with the pragma")),
+            v => Err(invalid(&format!("unsupported CAR version {}", v))),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> anyhow::Error {
+    Error::InvalidCar(msg.into()).into()
+}
+
+fn read_header<R: Read>(reader: &mut R) -> Result<CarHeader> {
+    let header = read_section(reader)?.ok_or_else(|| invalid("missing header"))?;
+    decode_header(&header)
+}
+
+fn decode_header(header: &[u8]) -> Result<CarHeader> {
+    forest_encoding::from_slice(header)
+        .map_err(|e| invalid(&format!("failed to decode header: {}", e)))
+}
+
+/// Reads the blocks of a CARv1 following its header, then finds the path to the leaf.
+fn read_blocks<R: Read>(mut reader: R, roots: Vec<Cid>, leaf: &Cid) -> Result<Proof> {
+    let mut blocks = HashMap::new();
+    while let Some(section) = read_section(&mut reader)? {
+        let cid = Cid::read_bytes(section.as_slice())
+            .map_err(|e| invalid(&format!("failed to decode block Cid: {}", e)))?;
+        let data = section[cid.to_bytes().len()..].to_vec();
+        check_cid(&data, &cid)?;
+        blocks.insert(cid, data);
+    }
+
+    // Breadth first search from the roots, so the path found is the shortest.
+    let mut parents: HashMap<Cid, Option<Cid>> = HashMap::new();
+    let mut queue = VecDeque::new();
+    for root in roots {
+        if blocks.contains_key(&root) && !parents.contains_key(&root) {
+            parents.insert(root, None);
+            queue.push_back(root);
+        }
+    }
+    while let Some(cid) = queue.pop_front() {
+        if &cid == leaf {
+            let mut nodes = Vec::new();
+            let mut next = Some(cid);
+            while let Some(cid) = next {
+                nodes.push(blocks.remove(&cid).expect("visited blocks exist"));
+                next = parents[&cid];
+            }
+            return Ok(Proof { nodes });
+        }
+        for link in LinkScanner::from(&blocks[&cid]) {
+            if blocks.contains_key(&link) && !parents.contains_key(&link) {
+                parents.insert(link, Some(cid));
+                queue.push_back(link);
+            }
+        }
+    }
+    Err(invalid(&format!(
+        "blocks do not form a chain from a root to {}",
+        leaf
+    )))
+}
+
+/// Reads a section of a CAR, returning `None` at the end of the reader.
+fn read_section<R: Read>(reader: &mut R) -> Result<Option<Vec<u8>>> {
+    match read_varint(reader)? {
+        Some((len, _)) => Ok(Some(read_exact(reader, len)?)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the varint length of a section, along with the number of bytes it is encoded in.
+/// Returns `None` at the end of the reader.
+fn read_varint<R: Read>(reader: &mut R) -> Result<Option<(u64, usize)>> {
+    let mut len = 0u64;
+    for i in 0..10 {
+        let mut byte = [0u8];
+        if reader.read(&mut byte)? == 0 {
+            if i == 0 {
+                return Ok(None);
+            }
+            return Err(invalid("truncated section length"));
+        }
+        if i == 9 && byte[0] > 1 {
+            return Err(invalid("section length overflows"));
+        }
+        len |= ((byte[0] & 0x7f) as u64) << (7 * i);
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some((len, i + 1)));
+        }
+    }
+    Err(invalid("section length overflows"))
+}
+
+/// Reads the data of a section with the length.
+fn read_exact<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>> {
+    let mut section = Vec::new();
+    reader.take(len).read_to_end(&mut section)?;
+    if section.len() as u64 != len {
+        return Err(invalid("truncated section"));
+    }
+    Ok(section)
+}
+
+/// Writes a section of a CAR, which is the varint length of the data followed by the data.
+fn write_section<W: Write>(writer: &mut W, data: &[&[u8]]) -> Result<()> {
+    let len: usize = data.iter().map(|d| d.len()).sum();
+    writer.write_all(&(len as u64).encode_var_vec())?;
+    for d in data {
+        writer.write_all(d)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProofGenerator;
+    use ipld_blockstore::BlockStore;
+
+    #[test]
+    fn car_export() {
+        let bs = forest_db::MemoryDB::default();
+        let leaf = bs.put(&"leaf", DEFAULT_HASH_CODE).unwrap();
+        let middle = bs.put(&(leaf, 8u8), DEFAULT_HASH_CODE).unwrap();
+        let root = bs.put(&(middle, "root"), DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let (middle_cid, _): (Cid, String) = p_gen.get(&root).unwrap().unwrap();
+        let (leaf_cid, _): (Cid, u8) = p_gen.get(&middle_cid).unwrap().unwrap();
+        let value: String = p_gen.get(&leaf_cid).unwrap().unwrap();
+        let proof = p_gen.generate_proof_to_cid(&value, &root).unwrap();
+
+        let car = proof.to_car();
+        let (len, read) = u64::decode_var(&car).unwrap();
+        let mut rest = &car[read + len as usize..];
+        let header: CarHeader =
+            forest_encoding::from_slice(&car[read..read + len as usize]).unwrap();
+        assert_eq!(
+            header,
+            CarHeader {
+                roots: vec![root],
+                version: 1
+            }
+        );
+
+        let mut blocks = Vec::new();
+        while !rest.is_empty() {
+            let (len, read) = u64::decode_var(rest).unwrap();
+            let section = &rest[read..read + len as usize];
+            let cid = Cid::read_bytes(section).unwrap();
+            let data = &section[cid.to_bytes().len()..];
+            assert_eq!(cid::new_from_cbor(data, DEFAULT_HASH_CODE), cid);
+            blocks.push(cid);
+            rest = &rest[read + len as usize..];
+        }
+        assert_eq!(blocks, vec![root, middle, leaf]);
+
+        let mut written = Vec::new();
+        proof.write_car(&mut written).unwrap();
+        assert_eq!(written, car);
+    }
+
+    #[test]
+    fn car_import() {
+        let bs = forest_db::MemoryDB::default();
+        let leaf = bs.put(&"leaf", DEFAULT_HASH_CODE).unwrap();
+        let other = bs.put(&"other", DEFAULT_HASH_CODE).unwrap();
+        let middle = bs.put(&(leaf, other), DEFAULT_HASH_CODE).unwrap();
+        let root = bs.put(&(middle, "root"), DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let (middle_cid, _): (Cid, String) = p_gen.get(&root).unwrap().unwrap();
+        let (leaf_cid, _): (Cid, Cid) = p_gen.get(&middle_cid).unwrap().unwrap();
+        let value: String = p_gen.get(&leaf_cid).unwrap().unwrap();
+        let proof = p_gen.generate_proof_to_cid(&value, &root).unwrap();
+
+        let car = proof.to_car();
+        assert_eq!(Proof::from_car(car.as_slice(), &leaf).unwrap(), proof);
+        let partial = Proof::from_car(car.as_slice(), &middle).unwrap();
+        assert_eq!(partial.nodes(), &proof.nodes()[1..]);
+        assert!(Proof::from_car(car.as_slice(), &other).is_err());
+
+        // CARv2 wraps the CARv1 after a pragma and header, with padding before the data.
+        let mut v2 = CARV2_PRAGMA.to_vec();
+        let data_offset = 11 + 40 + 5;
+        let mut header = [0u8; CARV2_HEADER_LEN];
+        LittleEndian::write_u64(&mut header[16..24], data_offset as u64);
+        LittleEndian::write_u64(&mut header[24..32], car.len() as u64);
+        v2.extend_from_slice(&header);
+        v2.extend_from_slice(&[0; 5]);
+        v2.extend_from_slice(&car);
+        v2.extend_from_slice(b"index");
+        assert_eq!(Proof::from_car(v2.as_slice(), &leaf).unwrap(), proof);
+
+        // Headers with a version of 2 other than the exact pragma are rejected.
+        let mut with_roots = Vec::new();
+        let header = forest_encoding::to_vec(&CarHeader {
+            roots: vec![root],
+            version: 2,
+        })
+        .unwrap();
+        write_section(&mut with_roots, &[&header]).unwrap();
+        with_roots.extend_from_slice(&v2[CARV2_PRAGMA.len()..]);
+        assert!(Proof::from_car(with_roots.as_slice(), &leaf).is_err());
+        let mut long_len = vec![0x8a, 0x00];
+        long_len.extend_from_slice(&v2[1..]);
+        assert!(Proof::from_car(long_len.as_slice(), &leaf).is_err());
+
+        // Blocks which do not match their Cid are rejected.
+        let mut tampered = car.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert!(Proof::from_car(tampered.as_slice(), &leaf).is_err());
+
+        // Chain is broken when a block on the path is missing.
+        let missing = Proof {
+            nodes: vec![proof.nodes()[0].clone(), proof.nodes()[2].clone()],
+        };
+        let mut car = Vec::new();
+        write_section(
+            &mut car,
+            &[&forest_encoding::to_vec(&CarHeader {
+                roots: vec![root],
+                version: 1,
+            })
+            .unwrap()],
+        )
+        .unwrap();
+        for node in missing.nodes() {
+            let cid = cid::new_from_cbor(node, DEFAULT_HASH_CODE);
+            write_section(&mut car, &[&cid.to_bytes(), node]).unwrap();
+        }
+        assert!(Proof::from_car(car.as_slice(), &leaf).is_err());
+        assert!(Proof::from_car(&car[..car.len() - 1], &leaf).is_err());
+    }
+
+    #[test]
+    fn section_length() {
+        let mut max = vec![0xff; 9];
+        max.push(0x01);
+        assert_eq!(
+            read_varint(&mut max.as_slice()).unwrap(),
+            Some((u64::MAX, 10))
+        );
+        // Tenth byte can only hold the top bit of the length.
+        max[9] = 0x02;
+        assert!(read_varint(&mut max.as_slice()).is_err());
+        max[9] = 0x81;
+        max.push(0x01);
+        assert!(read_varint(&mut max.as_slice()).is_err());
+        assert_eq!(
+            read_varint(&mut [0x80, 0x01].as_ref()).unwrap(),
+            Some((128, 2))
+        );
+        assert_eq!(read_varint(&mut [].as_ref()).unwrap(), None);
+        assert!(read_varint(&mut [0x80].as_ref()).is_err());
+    }
+}
@@ -0,0 +1,990 @@
+use crate::hasher::hash_cid;
+use crate::DEFAULT_HASH_CODE;
+use crate::{
+    amt, bigint, check_cid_with, load_bytes, Address, Blake2bHasher, CompressedProof, Error, Proof,
+    ProofGenerator, ProofHasher, StateProof, TokenAmount,
+};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::{repr::*, serde_bytes, tuple::*, BytesDe, BytesSer};
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Ticket generated from the VRF of the block producer.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct Ticket {
+    #[serde(with = "serde_bytes")]
+    pub vrfproof: Vec<u8>,
+}
+
+/// Proof that the block producer was elected to produce a block.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ElectionProof {
+    pub win_count: i64,
+    #[serde(with = "serde_bytes")]
+    pub vrfproof: Vec<u8>,
+}
+
+/// Entry of the drand randomness beacon.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct BeaconEntry {
+    pub round: u64,
+    #[serde(with = "serde_bytes")]
+    pub data: Vec<u8>,
+}
+
+/// Proof of spacetime, used for the winning PoSt of a block.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct PoStProof {
+    pub post_proof: i64,
+    #[serde(with = "serde_bytes")]
+    pub proof_bytes: Vec<u8>,
+}
+
+/// Signature bytes, including the leading signature type byte.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Signature(#[serde(with = "serde_bytes")] pub Vec<u8>);
+
+/// Filecoin block header. All fields are included, so that serializing the header gives the
+/// same bytes, and therefore the same [Cid], as the header it was decoded from.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct BlockHeader {
+    pub miner: Address,
+    pub ticket: Option<Ticket>,
+    pub election_proof: Option<ElectionProof>,
+    pub beacon_entries: Vec<BeaconEntry>,
+    pub win_post_proof: Vec<PoStProof>,
+    pub parents: Vec<Cid>,
+    #[serde(with = "bigint")]
+    pub parent_weight: i128,
+    pub epoch: i64,
+    pub parent_state_root: Cid,
+    pub parent_message_receipts: Cid,
+    pub messages: Cid,
+    pub bls_aggregate: Option<Signature>,
+    pub timestamp: u64,
+    pub signature: Option<Signature>,
+    pub fork_signal: u64,
+    #[serde(with = "bigint")]
+    pub parent_base_fee: TokenAmount,
+}
+
+impl BlockHeader {
+    /// Decodes a block header from its serialized bytes.
+    pub fn from_bytes(bz: &[u8]) -> Result<Self> {
+        Ok(forest_encoding::from_slice(bz)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode block header: {}", e)))?)
+    }
+}
+
+/// Roots of the message AMTs of a block, linked from the `Messages` field of the block header.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct TxMeta {
+    pub bls_messages: Cid,
+    pub secp_messages: Cid,
+}
+
+/// Message list of a block, each of which is an AMT of message [Cid]s.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum MessageList {
+    /// Unsigned messages, signed with an aggregated BLS signature in the header.
+    Bls = 0,
+    /// Signed messages from secp256k1 addresses.
+    Secp = 1,
+}
+
+/// Location of a message within a block, returned from verifying a [MessageProof].
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageInclusion {
+    /// [Cid] of the unsigned or signed message.
+    pub message: Cid,
+    /// List the message was included in.
+    pub list: MessageList,
+    /// Index of the message within the list.
+    pub index: u64,
+}
+
+/// Proof of data in the state tree of a block header's `ParentStateRoot`, starting from the
+/// block header.
+///
+/// The header is decoded when verifying, so the state segment is only accepted if it is rooted at
+/// the `ParentStateRoot`, and not any other [Cid] in the header.
+#[derive(Debug, PartialEq)]
+pub struct HeaderProof<P> {
+    header: Vec<u8>,
+    state: P,
+}
+
+impl<P: Serialize> Serialize for HeaderProof<P> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        (BytesSer(&self.header), &self.state).serialize(serializer)
+    }
+}
+
+impl<'de, P: Deserialize<'de>> Deserialize<'de> for HeaderProof<P> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (BytesDe(header), state) = Deserialize::deserialize(deserializer)?;
+        Ok(Self { header, state })
+    }
+}
+
+impl<P> HeaderProof<P>
+where
+    P: StateProof,
+{
+    /// Verifies the proof against the trusted block header [Cid]. Returns the decoded header
+    /// along with the output of verifying the state segment.
+    pub fn verify(&self, header: &Cid) -> Result<(BlockHeader, P::Output)> {
+        self.verify_with(header, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the trusted block header [Cid], computing the [Cid] of each
+    /// node with the hasher.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        header: &Cid,
+        hasher: &H,
+    ) -> Result<(BlockHeader, P::Output)> {
+        check_cid_with(&self.header, header, hasher)?;
+        let decoded = BlockHeader::from_bytes(&self.header)?;
+        let output = self
+            .state
+            .verify_state_with(&decoded.parent_state_root, hasher)?;
+        Ok((decoded, output))
+    }
+}
+
+impl<P> HeaderProof<P> {
+    /// Returns the [Cid] root of the proof, which is the block header.
+    pub fn root(&self) -> Cid {
+        cid::new_from_cbor(&self.header, DEFAULT_HASH_CODE)
+    }
+
+    /// Returns the serialized bytes of the block header.
+    pub fn header(&self) -> &[u8] {
+        &self.header
+    }
+
+    /// Returns reference to the segment proving data within the state tree.
+    pub fn state_proof(&self) -> &P {
+        &self.state
+    }
+}
+
+/// Receipt of the execution of a message.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct Receipt {
+    pub exit_code: i64,
+    #[serde(with = "serde_bytes")]
+    pub return_data: Vec<u8>,
+    pub gas_used: i64,
+    /// Root of the AMT of events emitted, only included in newer receipts.
+    #[serde(default)]
+    pub events_root: Option<Cid>,
+}
+
+impl Receipt {
+    /// Returns true if the message was executed successfully.
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+/// Proof of the inclusion of a message in a block. The proof nodes go from the block header
+/// through the `Messages` TxMeta and the AMT of the list that includes the message.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct MessageProof {
+    list: MessageList,
+    index: u64,
+    proof: Proof,
+}
+
+impl MessageProof {
+    /// Verifies the proof against the trusted block header [Cid] and returns the message [Cid]
+    /// along with the list and index the message was included at.
+    pub fn verify(&self, header: &Cid) -> Result<MessageInclusion> {
+        self.verify_with(header, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the trusted block header [Cid], computing the [Cid] of each
+    /// node with the hasher.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        header: &Cid,
+        hasher: &H,
+    ) -> Result<MessageInclusion> {
+        let mut nodes = self.proof.nodes().iter().rev();
+        let meta_root = decode_header(nodes.next(), header, hasher)?.messages;
+
+        let meta_node = nodes.next().ok_or(Error::IncompleteProof)?;
+        check_cid_with(meta_node, &meta_root, hasher)?;
+        let meta: TxMeta = forest_encoding::from_slice(meta_node)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode TxMeta: {}", e)))?;
+        let root = match self.list {
+            MessageList::Bls => meta.bls_messages,
+            MessageList::Secp => meta.secp_messages,
+        };
+
+        match amt::verify(nodes, &root, self.index, hasher)? {
+            Some(Ipld::Link(message)) => Ok(MessageInclusion {
+                message,
+                list: self.list,
+                index: self.index,
+            }),
+            Some(_) => Err(Error::InvalidNode("message AMT value is not a Cid".into()).into()),
+            None => Err(Error::KeyNotFound.into()),
+        }
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+/// Proof of the receipt at an index of a block header's `ParentMessageReceipts`. The receipts
+/// are for the messages of the parent tipset, in the order they were executed.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ReceiptProof {
+    index: u64,
+    proof: Proof,
+}
+
+impl ReceiptProof {
+    /// Verifies the proof against the trusted block header [Cid] and returns the receipt.
+    pub fn verify(&self, header: &Cid) -> Result<Receipt> {
+        self.verify_with(header, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the trusted block header [Cid], computing the [Cid] of each
+    /// node with the hasher, and returns the receipt.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        header: &Cid,
+        hasher: &H,
+    ) -> Result<Receipt> {
+        let mut nodes = self.proof.nodes().iter().rev();
+        let receipts = decode_header(nodes.next(), header, hasher)?.parent_message_receipts;
+        let value = amt::verify(nodes, &receipts, self.index, hasher)?.ok_or(Error::KeyNotFound)?;
+        Ok(forest_ipld::from_ipld(&value)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode receipt: {}", e)))?)
+    }
+
+    /// Returns the index of the receipt, which is the index of the message in the parent tipset.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+/// Event emitted by an actor during the execution of a message.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct StampedEvent {
+    /// ID of the actor which emitted the event.
+    pub emitter: u64,
+    pub entries: Vec<EventEntry>,
+}
+
+impl StampedEvent {
+    /// Returns the values of the event's topics, in order. For events emitted by the EVM these
+    /// are the log's topics.
+    pub fn topics(&self) -> Vec<&[u8]> {
+        let mut topics: Vec<_> = self
+            .entries
+            .iter()
+            .filter_map(|e| match e.key.strip_prefix('t')?.parse::<u8>() {
+                Ok(n) if (1..=4).contains(&n) => Some((n, e.value.as_slice())),
+                _ => None,
+            })
+            .collect();
+        topics.sort_by_key(|(n, _)| *n);
+        topics.into_iter().map(|(_, v)| v).collect()
+    }
+
+    /// Returns the value of the event's data entry. For events emitted by the EVM this is the
+    /// log's data.
+    pub fn data(&self) -> Option<&[u8]> {
+        self.entries
+            .iter()
+            .find(|e| e.key == "d")
+            .map(|e| e.value.as_slice())
+    }
+}
+
+/// Entry of a [StampedEvent].
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct EventEntry {
+    /// Flags which indicate how the entry should be indexed.
+    pub flags: u64,
+    /// Key of the entry, which for EVM logs is `t1` to `t4` for topics and `d` for data.
+    pub key: String,
+    /// Multicodec of the value, which is raw for EVM topics and data.
+    pub codec: u64,
+    #[serde(with = "serde_bytes")]
+    pub value: Vec<u8>,
+}
+
+/// Proof of the event at an index of a receipt's `events_root` AMT. This chains the proof of the
+/// receipt from the block header.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct EventProof {
+    receipt: ReceiptProof,
+    index: u64,
+    proof: Proof,
+}
+
+impl EventProof {
+    /// Verifies the proof against the trusted block header [Cid] and returns the receipt along
+    /// with the event.
+    pub fn verify(&self, header: &Cid) -> Result<(Receipt, StampedEvent)> {
+        self.verify_with(header, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the trusted block header [Cid], computing the [Cid] of each
+    /// node with the hasher, and returns the receipt along with the event.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        header: &Cid,
+        hasher: &H,
+    ) -> Result<(Receipt, StampedEvent)> {
+        let receipt = self.receipt.verify_with(header, hasher)?;
+        let events_root = receipt
+            .events_root
+            .ok_or_else(|| Error::InvalidNode("receipt has no events".into()))?;
+        let nodes = self.proof.nodes().iter().rev();
+        let value =
+            amt::verify(nodes, &events_root, self.index, hasher)?.ok_or(Error::KeyNotFound)?;
+        let event = forest_ipld::from_ipld(&value)
+            .map_err(|e| Error::InvalidNode(format!("failed to decode event: {}", e)))?;
+        Ok((receipt, event))
+    }
+
+    /// Returns the index of the event within the receipt's events.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Returns reference to the segment proving the receipt.
+    pub fn receipt_proof(&self) -> &ReceiptProof {
+        &self.receipt
+    }
+
+    /// Returns reference to the segment proving the event from the receipt's events root.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+/// Proof that a block header is an ancestor of a trusted block header. The proof contains a
+/// header for each epoch between the two, each linked through the `Parents` of the next.
+///
+/// Proof nodes are ordered from the ancestor to the trusted header.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct AncestryProof {
+    proof: Proof,
+}
+
+impl AncestryProof {
+    /// Verifies the proof against the trusted block header [Cid] and returns the ancestor block
+    /// header. Each header must be linked from the `Parents` of the header after it, and the
+    /// epochs must be strictly decreasing from the trusted header.
+    pub fn verify(&self, trusted: &Cid) -> Result<BlockHeader> {
+        self.verify_with(trusted, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the trusted block header [Cid], computing the [Cid] of each
+    /// header with the hasher, and returns the ancestor block header.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        trusted: &Cid,
+        hasher: &H,
+    ) -> Result<BlockHeader> {
+        let mut nodes = self.proof.nodes().iter().rev();
+        let mut header = decode_header(nodes.next(), trusted, hasher)?;
+        for node in nodes {
+            let cid = hash_cid(hasher, node);
+            if !header.parents.contains(&cid) {
+                return Err(Error::InvalidProof {
+                    link: cid,
+                    data: node.clone(),
+                }
+                .into());
+            }
+            let parent = BlockHeader::from_bytes(node)?;
+            if parent.epoch >= header.epoch {
+                return Err(Error::InvalidNode(format!(
+                    "parent epoch {} is not before epoch {}",
+                    parent.epoch, header.epoch
+                ))
+                .into());
+            }
+            header = parent;
+        }
+        Ok(header)
+    }
+
+    /// Returns the [Cid] of the ancestor block header.
+    pub fn ancestor(&self) -> Cid {
+        cid::new_from_cbor(&self.proof.nodes()[0], DEFAULT_HASH_CODE)
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Compresses the run of headers by removing the parent link from each header. The links
+    /// are recomputed from the headers when decompressing.
+    ///
+    /// Header bytes can not be removed, as they are needed to compute the [Cid] of each header.
+    pub fn compress(&self) -> Result<CompressedAncestryProof> {
+        Ok(CompressedAncestryProof {
+            proof: self.proof.compress()?,
+        })
+    }
+}
+
+/// Compressed [AncestryProof], where the serialized parent link to the previous header is
+/// removed from each header, ordered from the ancestor. The proof is self-contained, so it can
+/// be verified from only the trusted header [Cid].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct CompressedAncestryProof {
+    proof: CompressedProof,
+}
+
+impl CompressedAncestryProof {
+    /// Restores the ancestry proof, by inserting the link to the previous header into each header.
+    pub fn decompress(&self) -> Result<AncestryProof> {
+        Ok(AncestryProof {
+            proof: self.proof.decompress()?,
+        })
+    }
+
+    /// Decompresses and verifies the proof against the trusted block header [Cid], returning the
+    /// ancestor block header. As with [AncestryProof::verify], the epochs must be strictly
+    /// decreasing from the trusted header.
+    pub fn verify(&self, trusted: &Cid) -> Result<BlockHeader> {
+        self.verify_with(trusted, &Blake2bHasher)
+    }
+
+    /// Decompresses and verifies the proof against the trusted block header [Cid], computing
+    /// the [Cid] of each header with the hasher.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        trusted: &Cid,
+        hasher: &H,
+    ) -> Result<BlockHeader> {
+        self.decompress()?.verify_with(trusted, hasher)
+    }
+}
+
+/// Checks the first node against the block header [Cid] and decodes the header.
+fn decode_header<H: ProofHasher + ?Sized>(
+    node: Option<&Vec<u8>>,
+    header: &Cid,
+    hasher: &H,
+) -> Result<BlockHeader> {
+    let node = node.ok_or(Error::IncompleteProof)?;
+    check_cid_with(node, header, hasher)?;
+    BlockHeader::from_bytes(node)
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Loads the block header, tracking the header node.
+    pub fn get_header(&self, header: &Cid) -> Result<BlockHeader> {
+        BlockHeader::from_bytes(&load_bytes(self, header)?)
+    }
+
+    /// Generates a proof starting from the block header, which chains the proof of data within
+    /// the state tree of the header's `ParentStateRoot`.
+    pub fn generate_header_proof<P: StateProof>(
+        &self,
+        header: &Cid,
+        state: P,
+    ) -> Result<HeaderProof<P>> {
+        let bytes = load_bytes(self, header)?;
+        BlockHeader::from_bytes(&bytes)?;
+        Ok(HeaderProof {
+            header: bytes,
+            state,
+        })
+    }
+
+    /// Generates a proof of the inclusion of the message in the block. Both message lists of the
+    /// block are searched for the message [Cid].
+    pub fn generate_message_proof(&self, header: &Cid, message: &Cid) -> Result<MessageProof> {
+        let meta_root = self.get_header(header)?.messages;
+        let meta: TxMeta = forest_encoding::from_slice(&load_bytes(self, &meta_root)?)?;
+
+        let lists = [
+            (MessageList::Bls, meta.bls_messages),
+            (MessageList::Secp, meta.secp_messages),
+        ];
+        for (list, root) in lists.iter() {
+            let mut found = None;
+            amt::for_each(self, root, |i, v| {
+                if found.is_none() && v == &Ipld::Link(*message) {
+                    found = Some(i);
+                }
+                Ok(())
+            })?;
+            if let Some(index) = found {
+                let (_, leaf) = amt::lookup(self, root, index)?;
+                return Ok(MessageProof {
+                    list: *list,
+                    index,
+                    proof: self.generate_proof_raw(leaf, Some(header))?,
+                });
+            }
+        }
+        Err(Error::KeyNotFound.into())
+    }
+
+    /// Generates a proof of the receipt at the index of the block header's
+    /// `ParentMessageReceipts`.
+    pub fn generate_receipt_proof(&self, header: &Cid, index: u64) -> Result<ReceiptProof> {
+        let receipts = self.get_header(header)?.parent_message_receipts;
+        let (value, leaf) = amt::lookup(self, &receipts, index)?;
+        if value.is_none() {
+            return Err(Error::KeyNotFound.into());
+        }
+        Ok(ReceiptProof {
+            index,
+            proof: self.generate_proof_raw(leaf, Some(header))?,
+        })
+    }
+
+    /// Generates a proof of the event at the index of the events of the receipt at the index of
+    /// the block header's `ParentMessageReceipts`.
+    pub fn generate_event_proof(
+        &self,
+        header: &Cid,
+        receipt_index: u64,
+        index: u64,
+    ) -> Result<EventProof> {
+        let receipt = self.generate_receipt_proof(header, receipt_index)?;
+        let events_root = receipt
+            .verify(header)?
+            .events_root
+            .ok_or(Error::KeyNotFound)?;
+        let (value, leaf) = amt::lookup(self, &events_root, index)?;
+        if value.is_none() {
+            return Err(Error::KeyNotFound.into());
+        }
+        Ok(EventProof {
+            receipt,
+            index,
+            proof: self.generate_proof_raw(leaf, Some(&events_root))?,
+        })
+    }
+
+    /// Generates a proof that the block header `ancestor` is an ancestor of the `trusted` block
+    /// header. The proof follows the first parent of each header, which reaches the same tipsets
+    /// as any other parent, until a header links to the ancestor.
+    pub fn generate_ancestry_proof(&self, trusted: &Cid, ancestor: &Cid) -> Result<AncestryProof> {
+        let ancestor_bytes = load_bytes(self, ancestor)?;
+        let ancestor_epoch = BlockHeader::from_bytes(&ancestor_bytes)?.epoch;
+
+        let mut nodes = vec![load_bytes(self, trusted)?];
+        loop {
+            let header = BlockHeader::from_bytes(nodes.last().expect("trusted header included"))?;
+            if header.parents.contains(ancestor) {
+                break;
+            }
+            match header.parents.first() {
+                Some(parent) if header.epoch > ancestor_epoch => {
+                    nodes.push(load_bytes(self, parent)?)
+                }
+                _ => return Err(Error::KeyNotFound.into()),
+            }
+        }
+        nodes.push(ancestor_bytes);
+        nodes.reverse();
+
+        Ok(AncestryProof {
+            proof: Proof { nodes },
+        })
+    }
+}
+
+/// Creates a block header with the given links, with all other fields set to default values.
+#[cfg(test)]
+pub(crate) fn test_header(
+    epoch: i64,
+    parents: Vec<Cid>,
+    state_root: Cid,
+    messages: Cid,
+    receipts: Cid,
+) -> BlockHeader {
+    BlockHeader {
+        miner: Address::new_id(1000),
+        ticket: Some(Ticket {
+            vrfproof: vec![1; 96],
+        }),
+        election_proof: Some(ElectionProof {
+            win_count: 1,
+            vrfproof: vec![2; 96],
+        }),
+        beacon_entries: vec![BeaconEntry {
+            round: 1,
+            data: vec![3; 96],
+        }],
+        win_post_proof: vec![PoStProof {
+            post_proof: 3,
+            proof_bytes: vec![4; 192],
+        }],
+        parents,
+        parent_weight: 10_000,
+        epoch,
+        parent_state_root: state_root,
+        parent_message_receipts: receipts,
+        messages,
+        bls_aggregate: Some(Signature(vec![2; 97])),
+        timestamp: 1_598_306_400 + epoch as u64 * 30,
+        signature: Some(Signature(vec![2; 97])),
+        fork_signal: 0,
+        parent_base_fee: 100,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{build_state_tree, ActorState};
+
+    #[test]
+    fn header_to_actor_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let head = bs.put(&"head", DEFAULT_HASH_CODE).unwrap();
+        let addr = Address::new_id(100);
+        let actor = ActorState {
+            code: head,
+            head,
+            sequence: 2,
+            balance: 3,
+            delegated_address: None,
+        };
+        let state_root = build_state_tree(&bs, &[(addr.clone(), actor.clone())]);
+        let other_root = build_state_tree(
+            &bs,
+            &[(
+                addr.clone(),
+                ActorState {
+                    balance: 10,
+                    ..actor.clone()
+                },
+            )],
+        );
+
+        let header = test_header(10, vec![head], state_root, other_root, other_root);
+        let header_cid = bs.put(&header, DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        assert_eq!(p_gen.get_header(&header_cid).unwrap(), header);
+        let actor_proof = p_gen.generate_actor_proof(&state_root, &addr).unwrap();
+        let proof = p_gen
+            .generate_header_proof(&header_cid, actor_proof)
+            .unwrap();
+
+        let (decoded, proven) = proof.verify(&header_cid).unwrap();
+        assert_eq!(decoded, header);
+        assert_eq!(proven, actor);
+        assert_eq!(proof.root(), header_cid);
+
+        let bz = serde_cbor::to_vec(&proof).unwrap();
+        assert_eq!(
+            serde_cbor::from_slice::<HeaderProof<_>>(&bz).unwrap(),
+            proof
+        );
+
+        // A proof of the state tree linked through the messages field is rejected.
+        let wrong_field = p_gen
+            .generate_header_proof(
+                &header_cid,
+                p_gen.generate_actor_proof(&other_root, &addr).unwrap(),
+            )
+            .unwrap();
+        assert!(wrong_field.verify(&header_cid).is_err());
+    }
+
+    #[test]
+    fn header_to_generic_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let data = bs.put(&"data", DEFAULT_HASH_CODE).unwrap();
+        let state_root = bs.put(&(data,), DEFAULT_HASH_CODE).unwrap();
+        let messages = bs.put(&"messages", DEFAULT_HASH_CODE).unwrap();
+        let header = test_header(1, vec![], state_root, messages, messages);
+        let header_cid = bs.put(&header, DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let (data_cid,): (Cid,) = p_gen
+            .get(&p_gen.get_header(&header_cid).unwrap().parent_state_root)
+            .unwrap()
+            .unwrap();
+        let value: String = p_gen.get(&data_cid).unwrap().unwrap();
+        let state: Proof = p_gen.generate_proof_to_cid(&value, &state_root).unwrap();
+        let proof = p_gen.generate_header_proof(&header_cid, state).unwrap();
+        proof.verify(&header_cid).unwrap();
+        assert!(proof.verify(&state_root).is_err());
+    }
+
+    #[test]
+    fn message_inclusion() {
+        let bs = forest_db::MemoryDB::default();
+        let messages = |prefix: &str, n: usize| -> Vec<Ipld> {
+            (0..n)
+                .map(|i| {
+                    Ipld::Link(
+                        bs.put(&format!("{} {}", prefix, i), DEFAULT_HASH_CODE)
+                            .unwrap(),
+                    )
+                })
+                .collect()
+        };
+        let bls = messages("bls", 20);
+        let secp = messages("secp", 10);
+        let meta = TxMeta {
+            bls_messages: amt::build(&bs, &bls, None),
+            secp_messages: amt::build(&bs, &secp, None),
+        };
+        let meta_cid = bs.put(&meta, DEFAULT_HASH_CODE).unwrap();
+        let header = test_header(5, vec![], meta_cid, meta_cid, meta_cid);
+        let header_cid = bs.put(&header, DEFAULT_HASH_CODE).unwrap();
+
+        let cid_of = |v: &Ipld| match v {
+            Ipld::Link(c) => *c,
+            _ => unreachable!(),
+        };
+        for (list, index, message) in &[
+            (MessageList::Bls, 15, cid_of(&bls[15])),
+            (MessageList::Secp, 7, cid_of(&secp[7])),
+        ] {
+            let p_gen = ProofGenerator::new(&bs);
+            let proof = p_gen.generate_message_proof(&header_cid, message).unwrap();
+            let inclusion = proof.verify(&header_cid).unwrap();
+            assert_eq!(
+                inclusion,
+                MessageInclusion {
+                    message: *message,
+                    list: *list,
+                    index: *index,
+                }
+            );
+
+            let bz = serde_cbor::to_vec(&proof).unwrap();
+            assert_eq!(serde_cbor::from_slice::<MessageProof>(&bz).unwrap(), proof);
+
+            // Claiming the message is in the other list is rejected.
+            let forged = MessageProof {
+                list: match list {
+                    MessageList::Bls => MessageList::Secp,
+                    MessageList::Secp => MessageList::Bls,
+                },
+                ..proof
+            };
+            assert!(forged.verify(&header_cid).is_err());
+        }
+
+        let p_gen = ProofGenerator::new(&bs);
+        let missing = bs.put(&"not included", DEFAULT_HASH_CODE).unwrap();
+        assert!(p_gen.generate_message_proof(&header_cid, &missing).is_err());
+    }
+
+    #[test]
+    fn receipt_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let receipts: Vec<_> = (0..30)
+            .map(|i| {
+                forest_ipld::to_ipld(Receipt {
+                    exit_code: if i == 12 { 16 } else { 0 },
+                    return_data: vec![i as u8; 4],
+                    gas_used: 1_000 + i,
+                    events_root: None,
+                })
+                .unwrap()
+            })
+            .collect();
+        let receipts_root = amt::build(&bs, &receipts, None);
+        let head = bs.put(&"head", DEFAULT_HASH_CODE).unwrap();
+        let header = test_header(2, vec![], head, head, receipts_root);
+        let header_cid = bs.put(&header, DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_receipt_proof(&header_cid, 11).unwrap();
+        let receipt = proof.verify(&header_cid).unwrap();
+        assert!(receipt.succeeded());
+        assert_eq!(receipt.return_data, vec![11; 4]);
+        assert_eq!(receipt.gas_used, 1_011);
+
+        let failed = p_gen.generate_receipt_proof(&header_cid, 12).unwrap();
+        assert!(!failed.verify(&header_cid).unwrap().succeeded());
+
+        // Proof nodes do not include the receipts of other leaves.
+        let forged = ReceiptProof {
+            index: 3,
+            proof: failed.proof,
+        };
+        assert!(forged.verify(&header_cid).is_err());
+        assert!(p_gen.generate_receipt_proof(&header_cid, 30).is_err());
+    }
+
+    #[test]
+    fn event_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let event = |i: u64| StampedEvent {
+            emitter: 1000 + i,
+            entries: vec![
+                EventEntry {
+                    flags: 3,
+                    key: "t2".into(),
+                    codec: 0x55,
+                    value: vec![2; 32],
+                },
+                EventEntry {
+                    flags: 3,
+                    key: "t1".into(),
+                    codec: 0x55,
+                    value: vec![i as u8; 32],
+                },
+                EventEntry {
+                    flags: 3,
+                    key: "d".into(),
+                    codec: 0x55,
+                    value: vec![0xdd; 8],
+                },
+            ],
+        };
+        let events: Vec<_> = (0..40)
+            .map(|i| forest_ipld::to_ipld(event(i)).unwrap())
+            .collect();
+        let events_root = amt::build(&bs, &events, Some(5));
+        let receipts: Vec<_> = (0..3)
+            .map(|i| {
+                forest_ipld::to_ipld(Receipt {
+                    exit_code: 0,
+                    return_data: vec![],
+                    gas_used: i,
+                    events_root: if i == 1 { Some(events_root) } else { None },
+                })
+                .unwrap()
+            })
+            .collect();
+        let receipts_root = amt::build(&bs, &receipts, None);
+        let head = bs.put(&"head", DEFAULT_HASH_CODE).unwrap();
+        let header = test_header(2, vec![], head, head, receipts_root);
+        let header_cid = bs.put(&header, DEFAULT_HASH_CODE).unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_event_proof(&header_cid, 1, 37).unwrap();
+        let (receipt, proven) = proof.verify(&header_cid).unwrap();
+        assert_eq!(receipt.gas_used, 1);
+        assert_eq!(proven, event(37));
+        assert_eq!(proven.emitter, 1037);
+        assert_eq!(proven.topics(), vec![&[37; 32][..], &[2; 32][..]]);
+        assert_eq!(proven.data(), Some(&[0xdd; 8][..]));
+
+        let bz = serde_cbor::to_vec(&proof).unwrap();
+        assert_eq!(serde_cbor::from_slice::<EventProof>(&bz).unwrap(), proof);
+
+        // Proof nodes do not include the events of other leaves.
+        let forged = EventProof { index: 2, ..proof };
+        assert!(forged.verify(&header_cid).is_err());
+        assert!(p_gen.generate_event_proof(&header_cid, 1, 40).is_err());
+        assert!(p_gen.generate_event_proof(&header_cid, 0, 0).is_err());
+    }
+
+    #[test]
+    fn legacy_receipt() {
+        let bz = forest_encoding::to_vec(&(0u8, BytesDe(vec![1]), 10u64)).unwrap();
+        let receipt: Receipt = forest_encoding::from_slice(&bz).unwrap();
+        assert_eq!(receipt.events_root, None);
+        assert_eq!(receipt.gas_used, 10);
+    }
+
+    #[test]
+    fn ancestry_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let root = bs.put(&"state", DEFAULT_HASH_CODE).unwrap();
+
+        // Chain of tipsets with two blocks each and a null round at epoch 5.
+        let mut parents = vec![];
+        let mut chain = vec![];
+        for epoch in (0..10).filter(|e| *e != 5) {
+            let blocks: Vec<_> = (0..2)
+                .map(|i| {
+                    let mut header = test_header(epoch, parents.clone(), root, root, root);
+                    header.fork_signal = i;
+                    bs.put(&header, DEFAULT_HASH_CODE).unwrap()
+                })
+                .collect();
+            chain.push(blocks.clone());
+            parents = blocks;
+        }
+        let trusted = chain[8][1];
+        let ancestor = chain[1][1];
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_ancestry_proof(&trusted, &ancestor).unwrap();
+        assert_eq!(proof.proof().nodes().len(), 8);
+        assert_eq!(proof.ancestor(), ancestor);
+        let header = proof.verify(&trusted).unwrap();
+        assert_eq!(header.epoch, 1);
+        assert!(proof.verify(&chain[8][0]).is_err());
+
+        // Compressed proofs are verified from only the trusted header, without a store.
+        let compressed = proof.compress().unwrap();
+        assert_eq!(compressed.verify(&trusted).unwrap(), header);
+        assert_eq!(compressed.decompress().unwrap(), proof);
+        assert!(compressed.verify(&chain[8][0]).is_err());
+        let bz = serde_cbor::to_vec(&compressed).unwrap();
+        assert!(bz.len() < serde_cbor::to_vec(&proof).unwrap().len());
+        assert_eq!(
+            serde_cbor::from_slice::<CompressedAncestryProof>(&bz).unwrap(),
+            compressed
+        );
+
+        // Headers which are not ancestors can not be proven.
+        assert!(p_gen.generate_ancestry_proof(&ancestor, &trusted).is_err());
+    }
+
+    #[test]
+    fn ancestry_requires_decreasing_epochs() {
+        let bs = forest_db::MemoryDB::default();
+        let root = bs.put(&"state", DEFAULT_HASH_CODE).unwrap();
+        let parent = bs
+            .put(
+                &test_header(10, vec![], root, root, root),
+                DEFAULT_HASH_CODE,
+            )
+            .unwrap();
+        let child = bs
+            .put(
+                &test_header(10, vec![parent], root, root, root),
+                DEFAULT_HASH_CODE,
+            )
+            .unwrap();
+
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_ancestry_proof(&child, &parent).unwrap();
+        assert!(proof.verify(&child).is_err());
+        assert!(proof.compress().unwrap().verify(&child).is_err());
+    }
+}
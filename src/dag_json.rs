@@ -0,0 +1,106 @@
+//! Human readable DAG-JSON rendering of proofs, where each node is decoded and shown with its
+//! computed Cid.
+
+use crate::{check_cid, Error, Proof, DEFAULT_HASH_CODE};
+use anyhow::Result;
+use forest_ipld::json::{IpldJson, IpldJsonRef};
+use forest_ipld::Ipld;
+use serde::{Deserialize, Serialize};
+
+/// Node of a proof rendered as DAG-JSON, with links as `{"/": cid}` and bytes as
+/// `{"/": {"bytes": multibase}}`.
+#[derive(Serialize)]
+struct NodeJsonRef<'a> {
+    cid: IpldJsonRef<'a>,
+    node: IpldJsonRef<'a>,
+}
+
+#[derive(Deserialize)]
+struct NodeJson {
+    cid: IpldJson,
+    node: IpldJson,
+}
+
+impl Proof {
+    /// Renders the proof as a pretty printed DAG-JSON list of its nodes, ordered from the leaf to
+    /// the root, each with its computed Cid.
+    pub fn to_dag_json(&self) -> Result<String> {
+        let decoded = self
+            .nodes
+            .iter()
+            .map(|node| {
+                let cid = Ipld::Link(cid::new_from_cbor(node, DEFAULT_HASH_CODE));
+                let ipld: Ipld = forest_encoding::from_slice(node)
+                    .map_err(|e| Error::InvalidNode(format!("failed to decode node: {}", e)))?;
+                Ok((cid, ipld))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let nodes: Vec<_> = decoded
+            .iter()
+            .map(|(cid, node)| NodeJsonRef {
+                cid: IpldJsonRef(cid),
+                node: IpldJsonRef(node),
+            })
+            .collect();
+        Ok(serde_json::to_string_pretty(&nodes)?)
+    }
+
+    /// Parses a proof rendered by [Proof::to_dag_json]. Each node is encoded back to its bytes,
+    /// which must match the Cid shown for the node.
+    pub fn from_dag_json(json: &str) -> Result<Self> {
+        let parsed: Vec<NodeJson> = serde_json::from_str(json)
+            .map_err(|e| Error::InvalidDagJson(format!("failed to parse: {}", e)))?;
+        if parsed.is_empty() {
+            return Err(Error::InvalidDagJson("proof has no nodes".into()).into());
+        }
+        let nodes = parsed
+            .into_iter()
+            .map(|NodeJson { cid, node }| {
+                let cid = match cid.0 {
+                    Ipld::Link(cid) => cid,
+                    _ => return Err(Error::InvalidDagJson("node cid is not a link".into()).into()),
+                };
+                let bytes = forest_encoding::to_vec(&node.0)?;
+                check_cid(&bytes, &cid)?;
+                Ok(bytes)
+            })
+            .collect::<Result<_>>()?;
+        Ok(Proof { nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{amt, ProofGenerator};
+
+    #[test]
+    fn dag_json_round_trip() {
+        let bs = forest_db::MemoryDB::default();
+        let values: Vec<_> = (0..100)
+            .map(|i| {
+                Ipld::List(vec![
+                    Ipld::Integer(-i),
+                    Ipld::Bytes(vec![i as u8; 3]),
+                    Ipld::String(format!("value {}", i)),
+                    Ipld::Bool(i % 2 == 1),
+                    Ipld::Null,
+                ])
+            })
+            .collect();
+        let root = amt::build(&bs, &values, None);
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_amt_proof(&root, 77).unwrap();
+        let proof = proof.proof();
+
+        let json = proof.to_dag_json().unwrap();
+        assert!(json.contains(&format!("\"/\": \"{}\"", root)));
+        assert!(json.contains("\"value 77\""));
+        assert_eq!(&Proof::from_dag_json(&json).unwrap(), proof);
+
+        // Edited nodes no longer match their Cid.
+        let edited = json.replace("\"value 77\"", "\"value 78\"");
+        assert!(Proof::from_dag_json(&edited).is_err());
+        assert!(Proof::from_dag_json("[]").is_err());
+    }
+}
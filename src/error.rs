@@ -10,4 +10,26 @@ pub enum Error {
     NodeNotFound,
     #[error("Invalid proof, Cid {link:} not found in node: {data:?}")]
     InvalidProof { link: Cid, data: Vec<u8> },
+    #[error("Block {0} not found in store")]
+    BlockNotFound(Cid),
+    #[error("Invalid proof, expected node with Cid {expected:} but found {found:}")]
+    LinkMismatch { expected: Cid, found: Cid },
+    #[error("Invalid proof, proof ended before reaching the value")]
+    IncompleteProof,
+    #[error("Invalid proof, proof contains nodes after the value")]
+    UnexpectedNodes,
+    #[error("Key not found")]
+    KeyNotFound,
+    #[error("Invalid node: {0}")]
+    InvalidNode(String),
+    #[error("Invalid address: {0}")]
+    InvalidAddress(&'static str),
+    #[error("Invalid proof envelope: {0}")]
+    InvalidEnvelope(String),
+    #[error("Invalid DAG-JSON: {0}")]
+    InvalidDagJson(String),
+    #[error("Invalid CAR: {0}")]
+    InvalidCar(String),
+    #[error("Invalid eth_getProof response: {0}")]
+    InvalidEthProof(String),
 }
@@ -1,5 +1,8 @@
-use crate::{link_scanner::LinkScanner, Error, Proof, DEFAULT_HASH_CODE};
-use anyhow::Result;
+use crate::{
+    hasher::hash_cid, link_scanner::LinkScanner, Blake2bHasher, Error, HintedProof, Proof,
+    ProofHasher,
+};
+use anyhow::{anyhow, Result};
 use cid::{Cid, Code};
 use forest_db::{Error as DbError, Store};
 use ipld_blockstore::BlockStore;
@@ -51,7 +54,7 @@ use std::{collections::HashMap, error::Error as StdError};
 /// assert_eq!(proof.nodes().len(), 3);
 /// assert_eq!(proof.root(), root);
 /// proof.validate().unwrap();
-
+///
 /// // Or generate only to a specific node
 /// let proof = p_gen.generate_proof_to_cid(&"Some data", &b).unwrap();
 /// assert_eq!(proof.nodes().len(), 2);
@@ -59,9 +62,10 @@ use std::{collections::HashMap, error::Error as StdError};
 /// proof.validate().unwrap();
 /// ```
 #[derive(Debug)]
-pub struct ProofGenerator<'s, BS> {
+pub struct ProofGenerator<'s, BS, H = Blake2bHasher> {
     base: &'s BS,
     visited: RefCell<HashMap<Cid, Vec<u8>>>,
+    hasher: H,
 }
 
 impl<'bs, BS> ProofGenerator<'bs, BS>
@@ -69,12 +73,36 @@ where
     BS: BlockStore,
 {
     pub fn new(base: &'bs BS) -> Self {
+        Self::with_hasher(base, Blake2bHasher)
+    }
+}
+
+impl<'bs, BS, H> ProofGenerator<'bs, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Creates a proof generator which computes the [Cid] of the nodes of generated proofs with
+    /// the hasher, rather than the default Blake2b-256 implementation.
+    pub fn with_hasher(base: &'bs BS, hasher: H) -> Self {
         Self {
             base,
             visited: Default::default(),
+            hasher,
         }
     }
 
+    /// Returns the hasher used to compute the [Cid] of nodes.
+    pub fn hasher(&self) -> &H {
+        &self.hasher
+    }
+
+    /// Creates a generator over this one with the same hasher, which tracks only the nodes
+    /// loaded through it while still tracking them in this generator.
+    pub(crate) fn nested(&self) -> ProofGenerator<'_, Self, &H> {
+        ProofGenerator::with_hasher(self, &self.hasher)
+    }
+
     /// Generates a proof with the raw serialized bytes of the element being proven. This
     /// will use as many nodes as it can connect since the tracking started.
     pub fn generate_proof<I: Serialize>(&self, proof_item: &I) -> Result<Proof> {
@@ -95,7 +123,42 @@ where
     /// This does not currently generate a canonical or shortest proof, this will just find
     /// the first connection.
     pub fn generate_proof_raw(&self, bytes: Vec<u8>, root: Option<&Cid>) -> Result<Proof> {
-        let mut current_cid = cid::new_from_cbor(&bytes, DEFAULT_HASH_CODE);
+        let (nodes, _) = self.generate_path(bytes, root)?;
+        Ok(Proof { nodes })
+    }
+
+    /// Generates a proof of the item along with the position of the link to each node in its
+    /// parent, which allows the proof to be validated without scanning the parents for links.
+    pub fn generate_hinted_proof<I: Serialize>(
+        &self,
+        proof_item: &I,
+        root: Option<&Cid>,
+    ) -> Result<HintedProof> {
+        self.generate_hinted_proof_raw(serde_cbor::to_vec(proof_item)?, root)
+    }
+
+    /// Generates a proof with the raw serialized bytes of the element being proven, along with
+    /// the position of the link to each node in its parent.
+    pub fn generate_hinted_proof_raw(
+        &self,
+        bytes: Vec<u8>,
+        root: Option<&Cid>,
+    ) -> Result<HintedProof> {
+        let (nodes, hints) = self.generate_path(bytes, root)?;
+        Ok(HintedProof {
+            proof: Proof { nodes },
+            hints,
+        })
+    }
+
+    /// Finds the nodes linking the bytes to the root, along with the byte offset of each link
+    /// in the node it is found in.
+    fn generate_path(
+        &self,
+        bytes: Vec<u8>,
+        root: Option<&Cid>,
+    ) -> Result<(Vec<Vec<u8>>, Vec<u64>)> {
+        let mut current_cid = hash_cid(&self.hasher, &bytes);
         if !self.visited.borrow().contains_key(&current_cid) {
             return Err(Error::NodeNotFound.into());
         }
@@ -106,11 +169,12 @@ where
 
         let mut proof_nodes = Vec::with_capacity(total_nodes);
         proof_nodes.push(bytes);
+        let mut hints = Vec::with_capacity(total_nodes);
 
         // Keeps track of all nodes which link to the key node.
         // All Nodes in the `Vec` link to the hashmap key `Cid`.
         //* This can be modified to keep track of all links and compute shortest canonical path.
-        let mut scan_cache = HashMap::<Cid, (Cid, Vec<u8>)>::with_capacity(total_nodes);
+        let mut scan_cache = HashMap::<Cid, (Cid, Vec<u8>, u64)>::with_capacity(total_nodes);
 
         'proof: loop {
             if let Some(r) = root {
@@ -119,9 +183,10 @@ where
                 }
             }
 
-            if let Some((c_cid, c_bytes)) = scan_cache.remove(&current_cid) {
+            if let Some((c_cid, c_bytes, c_offset)) = scan_cache.remove(&current_cid) {
                 // Link has been scanned already, push the cached node and update the current cid.
                 proof_nodes.push(c_bytes);
+                hints.push(c_offset);
                 current_cid = c_cid;
                 continue 'proof;
             }
@@ -129,42 +194,51 @@ where
             // Scan for links until one is found to be connected.
             for (u_cid, u_bytes) in &mut unvisited_nodes {
                 // Create iterator which scans over links lazily.
-                let scanner = LinkScanner::from(u_bytes);
+                let mut scanner = LinkScanner::from(u_bytes);
 
                 // Iterate through links: use node if it links to current node add to cache if not.
-                let mut link_buffer = SmallVec::<[Cid; 8]>::new();
-                for link in scanner {
+                let mut link_buffer = SmallVec::<[(Cid, u64); 8]>::new();
+                while let Some(link) = scanner.next() {
                     if link == current_cid {
                         // The current node's link was found in another node, include to proof
                         // chain and discard other links found. The other links can be discarded
                         // because the Ipld graph is acyclic.
                         proof_nodes.push(u_bytes.clone());
+                        hints.push(scanner.link_offset());
                         current_cid = *u_cid;
                         continue 'proof;
                     }
 
                     // Push link found to buffer, will be added to cache if not found in node.
-                    link_buffer.push(link);
+                    link_buffer.push((link, scanner.link_offset()));
                 }
 
-                for link in link_buffer {
+                for (link, offset) in link_buffer {
                     //* This can be modified to keep the smaller node, but this doesn't matter
                     scan_cache
                         .entry(link)
-                        .or_insert_with(|| (*u_cid, u_bytes.clone()));
+                        .or_insert_with(|| (*u_cid, u_bytes.clone(), offset));
                 }
             }
 
             break;
         }
 
-        Ok(Proof { nodes: proof_nodes })
+        Ok((proof_nodes, hints))
     }
 }
 
-impl<BS> BlockStore for ProofGenerator<'_, BS>
+/// Loads the bytes of a block from the store, returning an error if it does not exist.
+pub(crate) fn load_bytes<BS: BlockStore>(bs: &BS, cid: &Cid) -> Result<Vec<u8>> {
+    bs.get_bytes(cid)
+        .map_err(|e| anyhow!(e.to_string()))?
+        .ok_or_else(|| Error::BlockNotFound(*cid).into())
+}
+
+impl<BS, H> BlockStore for ProofGenerator<'_, BS, H>
 where
     BS: BlockStore,
+    H: ProofHasher,
 {
     fn get_bytes(&self, cid: &Cid) -> Result<Option<Vec<u8>>, Box<dyn StdError>> {
         let bytes = self.base.get_bytes(cid)?;
@@ -180,7 +254,10 @@ where
     }
 
     fn put_raw(&self, bytes: Vec<u8>, code: Code) -> Result<Cid, Box<dyn StdError>> {
-        let cid = cid::new_from_cbor(&bytes, code);
+        let cid = match code {
+            Code::Blake2b256 => hash_cid(&self.hasher, &bytes),
+            _ => cid::new_from_cbor(&bytes, code),
+        };
         self.visited
             .borrow_mut()
             .entry(cid)
@@ -190,7 +267,7 @@ where
     }
 }
 
-impl<BS> Store for ProofGenerator<'_, BS>
+impl<BS, H> Store for ProofGenerator<'_, BS, H>
 where
     BS: Store,
 {
@@ -243,6 +320,7 @@ where
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DEFAULT_HASH_CODE;
     use forest_ipld::ipld;
 
     #[test]
@@ -303,6 +381,7 @@ mod tests {
         #[derive(Debug, serde::Deserialize)]
         struct TmpC {
             d: Cid,
+            #[allow(dead_code)]
             e: Cid,
         }
         let TmpC { d, .. } = p_gen.get(&c).unwrap().unwrap();
@@ -324,5 +403,125 @@ mod tests {
         assert_eq!(proof.nodes().len(), 3);
         assert_eq!(proof.root(), c);
         proof.validate().unwrap();
+
+        // Hinted proofs record where each link is in its parent.
+        let hinted = p_gen.generate_hinted_proof(&prove_node, None).unwrap();
+        assert_eq!(hinted.proof().nodes().len(), 4);
+        assert_eq!(hinted.hints(), &[2, 3, 87]);
+        hinted.validate().unwrap();
+    }
+
+    #[test]
+    fn custom_hasher() {
+        use crate::{build_state_tree, ActorState, Address, StateProof};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        /// Counts the nodes hashed, to check the hasher is used.
+        #[derive(Debug, Default, Clone)]
+        struct CountingHasher(Arc<AtomicUsize>);
+
+        impl CountingHasher {
+            fn count(&self) -> usize {
+                self.0.load(Ordering::SeqCst)
+            }
+        }
+
+        impl ProofHasher for CountingHasher {
+            fn blake2b_256(&self, data: &[u8]) -> [u8; 32] {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Blake2bHasher.blake2b_256(data)
+            }
+        }
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let bs = forest_db::MemoryDB::default();
+        let leaf = bs.put(&"leaf", DEFAULT_HASH_CODE).unwrap();
+        let root = bs.put(&ipld!([1u8, leaf]), DEFAULT_HASH_CODE).unwrap();
+
+        let hasher = CountingHasher::default();
+        let p_gen = ProofGenerator::with_hasher(&bs, hasher.clone());
+        assert_send(&p_gen);
+        let (_, leaf): (u8, Cid) = p_gen.get(&root).unwrap().unwrap();
+        let value: String = p_gen.get(&leaf).unwrap().unwrap();
+        let proof = p_gen.generate_proof(&value).unwrap();
+        assert_eq!(hasher.count(), 1);
+        assert_eq!(proof.root_with(p_gen.hasher()), root);
+        assert_eq!(hasher.count(), 2);
+
+        proof.validate_with(&hasher).unwrap();
+        assert_eq!(hasher.count(), 4);
+
+        // Puts are addressed with the hasher.
+        assert_eq!(p_gen.put(&"leaf", DEFAULT_HASH_CODE).unwrap(), leaf);
+        assert_eq!(hasher.count(), 5);
+
+        // Chained proofs through the actor's head, and nested generators, use the hasher.
+        let field = bs.put(&"info", DEFAULT_HASH_CODE).unwrap();
+        let head = bs.put(&(field, 1u8), DEFAULT_HASH_CODE).unwrap();
+        let addr = Address::new_id(1000);
+        let actor = ActorState {
+            code: head,
+            head,
+            sequence: 0,
+            balance: 0,
+            delegated_address: None,
+        };
+        let state_root = build_state_tree(&bs, &[(addr.clone(), actor.clone())]);
+
+        let hasher = CountingHasher::default();
+        let p_gen = ProofGenerator::with_hasher(&bs, hasher.clone());
+        let nested = p_gen.nested();
+        let actor_head = nested.get_actor(&state_root, &addr).unwrap().unwrap();
+        let (field, _): (Cid, u8) = nested.get(&actor_head.head).unwrap().unwrap();
+        let info: String = nested.get(&field).unwrap().unwrap();
+        let head_proof = nested.generate_proof_to_cid(&info, &head).unwrap();
+        assert_eq!(hasher.count(), 1);
+        head_proof.validate_with(nested.hasher()).unwrap();
+        assert_eq!(hasher.count(), 3);
+
+        let proof = p_gen
+            .generate_actor_head_proof(&state_root, &addr, &info)
+            .unwrap();
+        assert_eq!(hasher.count(), 5);
+        assert_eq!(proof.verify(&state_root).unwrap(), actor);
+        assert_eq!(hasher.count(), 5);
+
+        // Typed proofs are verified with the hasher.
+        assert_eq!(proof.verify_with(&state_root, &hasher).unwrap(), actor);
+        assert!(hasher.count() > 5);
+        let count = hasher.count();
+        let actor_proof = proof.actor_proof();
+        assert_eq!(
+            actor_proof.verify_state_with(&state_root, &hasher).unwrap(),
+            actor
+        );
+        assert!(hasher.count() > count);
+
+        // Hasher which does not compute Blake2b-256 does not match the links of the nodes.
+        #[derive(Debug)]
+        struct ZeroHasher;
+        impl ProofHasher for ZeroHasher {
+            fn blake2b_256(&self, _: &[u8]) -> [u8; 32] {
+                [0; 32]
+            }
+        }
+        assert!(head_proof.validate_with(&ZeroHasher).is_err());
+        assert!(proof.verify_with(&state_root, &ZeroHasher).is_err());
+        assert!(proof
+            .actor_proof()
+            .verify_state_with(&state_root, &ZeroHasher)
+            .is_err());
+        assert_ne!(head_proof.root_with(&ZeroHasher), head);
+        let p_gen = ProofGenerator::with_hasher(&bs, ZeroHasher);
+        p_gen.get::<String>(&leaf).unwrap();
+        assert!(p_gen.generate_proof(&value).is_err());
+        p_gen.get_actor(&state_root, &addr).unwrap();
+        p_gen.get::<(Cid, u8)>(&head).unwrap();
+        p_gen.get::<String>(&field).unwrap();
+        assert!(p_gen
+            .generate_actor_head_proof(&state_root, &addr, &info)
+            .is_err());
     }
 }
@@ -0,0 +1,279 @@
+//! Minimal read-only support for the Filecoin HAMT, used to generate and verify proofs of
+//! values keyed within the state tree and actor states.
+//!
+//! Nodes are encoded as `[bitfield, pointers]`, where each pointer is either a link to a child
+//! node or a bucket of `[key, value]` pairs. Keys are hashed with sha256 and the hash is consumed
+//! `bit_width` bits at a time, starting with the most significant bit.
+
+#[cfg(test)]
+use crate::DEFAULT_HASH_CODE;
+use crate::{check_cid_with, load_bytes, Error, ProofHasher};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::BytesDe;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+use sha2::{Digest, Sha256};
+
+/// Bit width used by the state tree and the HAMTs within the builtin actors.
+pub(crate) const DEFAULT_BIT_WIDTH: u32 = 5;
+
+/// Outcome of searching for a key within a single node.
+enum Lookup {
+    /// Key can only exist within the linked child node.
+    Link(Cid),
+    /// Value of the key, found in a bucket of the node.
+    Found(Ipld),
+    /// Key does not exist within the HAMT.
+    Absent,
+}
+
+/// Looks up `key` in the HAMT with root `root`, loading all nodes through the store. Returns the
+/// value if it exists, along with the bytes of the last node searched.
+pub(crate) fn lookup<BS: BlockStore>(
+    bs: &BS,
+    root: &Cid,
+    key: &[u8],
+    bit_width: u32,
+) -> Result<(Option<Ipld>, Vec<u8>)> {
+    let hash = hash_key(key);
+    let mut cid = *root;
+    let mut depth = 0;
+    loop {
+        let bytes = load_bytes(bs, &cid)?;
+        match search_node(&bytes, &hash, depth, key, bit_width)? {
+            Lookup::Link(child) => cid = child,
+            Lookup::Found(value) => return Ok((Some(value), bytes)),
+            Lookup::Absent => return Ok((None, bytes)),
+        }
+        depth += 1;
+    }
+}
+
+/// Verifies that the nodes, ordered from the root, are the path to `key` in the HAMT with root
+/// `root`. Returns the value of the key, or `None` if the nodes prove the key does not exist.
+pub(crate) fn verify<'a, I, H>(
+    nodes: I,
+    root: &Cid,
+    key: &[u8],
+    bit_width: u32,
+    hasher: &H,
+) -> Result<Option<Ipld>>
+where
+    I: IntoIterator<Item = &'a Vec<u8>>,
+    H: ProofHasher + ?Sized,
+{
+    let mut nodes = nodes.into_iter();
+    let value = verify_path(&mut nodes, root, key, bit_width, hasher)?;
+    if nodes.next().is_some() {
+        return Err(Error::UnexpectedNodes.into());
+    }
+    Ok(value)
+}
+
+/// Verifies the path to `key` like [verify], but only consumes the nodes of the path, so that
+/// proofs can continue into data linked from the value.
+pub(crate) fn verify_path<'a, I, H>(
+    nodes: &mut I,
+    root: &Cid,
+    key: &[u8],
+    bit_width: u32,
+    hasher: &H,
+) -> Result<Option<Ipld>>
+where
+    I: Iterator<Item = &'a Vec<u8>>,
+    H: ProofHasher + ?Sized,
+{
+    let hash = hash_key(key);
+    let mut expected = *root;
+    let mut depth = 0;
+    loop {
+        let node = nodes.next().ok_or(Error::IncompleteProof)?;
+        check_cid_with(node, &expected, hasher)?;
+        match search_node(node, &hash, depth, key, bit_width)? {
+            Lookup::Link(child) => {
+                expected = child;
+                depth += 1;
+            }
+            Lookup::Found(value) => return Ok(Some(value)),
+            Lookup::Absent => return Ok(None),
+        }
+    }
+}
+
+fn hash_key(key: &[u8]) -> Vec<u8> {
+    Sha256::digest(key).to_vec()
+}
+
+/// Reads `n` bits from the hash at the bit offset, most significant bit first.
+pub(crate) fn hash_bits(hash: &[u8], offset: u32, n: u32) -> Result<u32> {
+    if (offset + n) as usize > hash.len() * 8 {
+        return Err(Error::InvalidNode("maximum HAMT depth exceeded".into()).into());
+    }
+    let mut out = 0;
+    for i in offset..offset + n {
+        let bit = (hash[(i / 8) as usize] >> (7 - i % 8)) & 1;
+        out = (out << 1) | bit as u32;
+    }
+    Ok(out)
+}
+
+/// Checks the bit at the index of the bitfield, which is encoded as a big-endian integer.
+pub(crate) fn bit_set(bitfield: &[u8], idx: u32) -> bool {
+    let byte = (idx / 8) as usize;
+    byte < bitfield.len() && bitfield[bitfield.len() - 1 - byte] & (1 << (idx % 8)) != 0
+}
+
+fn search_node(
+    bytes: &[u8],
+    hash: &[u8],
+    depth: u32,
+    key: &[u8],
+    bit_width: u32,
+) -> Result<Lookup> {
+    let (bitfield, pointers): (BytesDe, Vec<Ipld>) = forest_encoding::from_slice(bytes)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode HAMT node: {}", e)))?;
+    let idx = hash_bits(hash, depth * bit_width, bit_width)?;
+    if !bit_set(&bitfield.0, idx) {
+        return Ok(Lookup::Absent);
+    }
+
+    let position = (0..idx).filter(|i| bit_set(&bitfield.0, *i)).count();
+    match pointers.get(position) {
+        Some(Ipld::Link(cid)) => Ok(Lookup::Link(*cid)),
+        Some(Ipld::List(bucket)) => {
+            for entry in bucket {
+                match entry {
+                    Ipld::List(kv) if kv.len() == 2 => {
+                        if kv[0] == Ipld::Bytes(key.to_vec()) {
+                            return Ok(Lookup::Found(kv[1].clone()));
+                        }
+                    }
+                    _ => return Err(Error::InvalidNode("invalid HAMT bucket entry".into()).into()),
+                }
+            }
+            Ok(Lookup::Absent)
+        }
+        _ => Err(Error::InvalidNode("invalid HAMT pointer".into()).into()),
+    }
+}
+
+/// Builds a HAMT from the entries, in the same canonical shape as the go and rust
+/// implementations, and returns the root Cid.
+#[cfg(test)]
+pub(crate) fn build<BS: BlockStore>(bs: &BS, entries: &[(Vec<u8>, Ipld)], bit_width: u32) -> Cid {
+    const MAX_BUCKET: usize = 3;
+    type Entry = (Vec<u8>, Ipld);
+
+    fn build_node<BS: BlockStore>(
+        bs: &BS,
+        entries: Vec<(Vec<u8>, &Entry)>,
+        depth: u32,
+        bit_width: u32,
+    ) -> Cid {
+        let mut groups = std::collections::BTreeMap::<u32, Vec<_>>::new();
+        for entry in entries {
+            let idx = hash_bits(&entry.0, depth * bit_width, bit_width).unwrap();
+            groups.entry(idx).or_default().push(entry);
+        }
+
+        let mut bitfield = [0u8; 32];
+        let mut pointers = Vec::new();
+        for (idx, mut group) in groups {
+            bitfield[31 - (idx / 8) as usize] |= 1 << (idx % 8);
+            if group.len() <= MAX_BUCKET {
+                group.sort_by(|a, b| (a.1).0.cmp(&(b.1).0));
+                let kvs = group
+                    .into_iter()
+                    .map(|(_, (k, v))| Ipld::List(vec![Ipld::Bytes(k.clone()), v.clone()]))
+                    .collect();
+                pointers.push(Ipld::List(kvs));
+            } else {
+                pointers.push(Ipld::Link(build_node(bs, group, depth + 1, bit_width)));
+            }
+        }
+        let start = bitfield.iter().position(|b| *b != 0).unwrap_or(32);
+        let node = Ipld::List(vec![
+            Ipld::Bytes(bitfield[start..].to_vec()),
+            Ipld::List(pointers),
+        ]);
+        bs.put(&node, DEFAULT_HASH_CODE).unwrap()
+    }
+
+    let hashed = entries.iter().map(|e| (hash_key(&e.0), e)).collect();
+    build_node(bs, hashed, 0, bit_width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProofGenerator;
+
+    fn entries(n: u64) -> Vec<(Vec<u8>, Ipld)> {
+        (0..n)
+            .map(|i| (i.to_be_bytes().to_vec(), Ipld::Integer(i as i128 * 2)))
+            .collect()
+    }
+
+    fn leaf_cid(proof: &crate::Proof) -> Cid {
+        cid::new_from_cbor(&proof.nodes()[0], DEFAULT_HASH_CODE)
+    }
+
+    #[test]
+    fn lookup_and_verify() {
+        let bs = forest_db::MemoryDB::default();
+        let root = build(&bs, &entries(200), DEFAULT_BIT_WIDTH);
+
+        for i in &[0u64, 7, 199] {
+            let key = i.to_be_bytes();
+            let p_gen = ProofGenerator::new(&bs);
+            let (value, leaf) = lookup(&p_gen, &root, &key, DEFAULT_BIT_WIDTH).unwrap();
+            assert_eq!(value, Some(Ipld::Integer(*i as i128 * 2)));
+
+            let proof = p_gen.generate_proof_raw(leaf, Some(&root)).unwrap();
+            assert!(proof.nodes().len() > 1);
+            assert_eq!(
+                verify(
+                    proof.nodes().iter().rev(),
+                    &root,
+                    &key,
+                    DEFAULT_BIT_WIDTH,
+                    p_gen.hasher()
+                )
+                .unwrap(),
+                value
+            );
+            // Nodes do not prove anything for a different root.
+            assert!(verify(
+                proof.nodes().iter().rev(),
+                &leaf_cid(&proof),
+                &key,
+                DEFAULT_BIT_WIDTH,
+                p_gen.hasher()
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn absent_key() {
+        let bs = forest_db::MemoryDB::default();
+        let root = build(&bs, &entries(4), DEFAULT_BIT_WIDTH);
+
+        let p_gen = ProofGenerator::new(&bs);
+        let (value, leaf) = lookup(&p_gen, &root, b"missing", DEFAULT_BIT_WIDTH).unwrap();
+        assert_eq!(value, None);
+        let proof = p_gen.generate_proof_raw(leaf, Some(&root)).unwrap();
+        assert_eq!(
+            verify(
+                proof.nodes().iter().rev(),
+                &root,
+                b"missing",
+                DEFAULT_BIT_WIDTH,
+                p_gen.hasher()
+            )
+            .unwrap(),
+            None
+        );
+    }
+}
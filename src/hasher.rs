@@ -0,0 +1,70 @@
+//! Hashing of proof nodes into their Cids, which can be replaced with another Blake2b-256
+//! implementation, such as the `hash` syscall within an FVM actor or one which is hardware
+//! accelerated.
+
+/// Multicodec code of DAG-CBOR.
+const DAG_CBOR: u8 = 0x71;
+/// Varint encoded multihash code of Blake2b-256, `0xb220`.
+const BLAKE2B_256: [u8; 3] = [0xa0, 0xe4, 0x02];
+/// Length of the Blake2b-256 digest.
+const DIGEST_LEN: usize = 32;
+
+/// Length of the bytes of the Cid of a proof node.
+pub const NODE_CID_LEN: usize = 6 + DIGEST_LEN;
+
+/// Hasher used to compute the Cids of proof nodes when generating and validating proofs.
+///
+/// Nodes are always addressed by a CIDv1 of DAG-CBOR with a Blake2b-256 multihash, so
+/// implementations must return the Blake2b-256 digest for proofs to be interchangeable.
+pub trait ProofHasher {
+    /// Returns the Blake2b-256 digest of the data.
+    fn blake2b_256(&self, data: &[u8]) -> [u8; DIGEST_LEN];
+
+    /// Returns the bytes of the Cid of a proof node.
+    fn node_cid(&self, node: &[u8]) -> [u8; NODE_CID_LEN] {
+        let mut cid = [0u8; NODE_CID_LEN];
+        cid[0] = 1;
+        cid[1] = DAG_CBOR;
+        cid[2..5].copy_from_slice(&BLAKE2B_256);
+        cid[5] = DIGEST_LEN as u8;
+        cid[6..].copy_from_slice(&self.blake2b_256(node));
+        cid
+    }
+}
+
+impl<H: ProofHasher + ?Sized> ProofHasher for &H {
+    fn blake2b_256(&self, data: &[u8]) -> [u8; DIGEST_LEN] {
+        (**self).blake2b_256(data)
+    }
+}
+
+/// Default [ProofHasher], which computes the same Cids as the Blake2b-256 multihash code.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Blake2bHasher;
+
+impl ProofHasher for Blake2bHasher {
+    fn blake2b_256(&self, data: &[u8]) -> [u8; DIGEST_LEN] {
+        let mut digest = [0u8; DIGEST_LEN];
+        digest.copy_from_slice(
+            blake2b_simd::Params::new()
+                .hash_length(DIGEST_LEN)
+                .hash(data)
+                .as_bytes(),
+        );
+        digest
+    }
+}
+
+/// Returns the bytes of the Cid of a proof node, which is a CIDv1 of the DAG-CBOR node hashed
+/// with Blake2b-256.
+pub fn node_cid(node: &[u8]) -> [u8; NODE_CID_LEN] {
+    Blake2bHasher.node_cid(node)
+}
+
+/// Returns the [Cid](cid::Cid) of a proof node computed with the hasher.
+#[cfg(feature = "std")]
+pub(crate) fn hash_cid<H: ProofHasher + ?Sized>(hasher: &H, node: &[u8]) -> cid::Cid {
+    use std::convert::TryFrom;
+
+    cid::Cid::try_from(&hasher.node_cid(node)[..]).expect("node Cid bytes are valid")
+}
@@ -0,0 +1,298 @@
+//! Minimal read-only support for the KAMT, the HAMT variant used by the EVM actor to store
+//! contract storage.
+//!
+//! Nodes are encoded like HAMT nodes, as `[bitfield, pointers]`, but keys are used as their own
+//! hash and links may carry an extension `[[consumed, path], link]`. An extension skips the
+//! levels of the tree where all keys share the same bits: the `consumed` bits after the
+//! pointer's index must match `path`, stored most significant bit first, to follow the link.
+//! Since the key length is not a multiple of the bit width, the index of the last level is read
+//! with the bits past the end of the key as zeros.
+
+use crate::hamt::bit_set;
+use crate::{check_cid_with, load_bytes, Error, ProofHasher};
+use anyhow::Result;
+use cid::Cid;
+use forest_encoding::BytesDe;
+use forest_ipld::Ipld;
+use ipld_blockstore::BlockStore;
+
+/// Bit width of the EVM actor's contract storage.
+pub(crate) const EVM_BIT_WIDTH: u32 = 5;
+
+/// Outcome of searching for a key within a single node.
+enum Lookup {
+    /// Key can only exist within the linked child node, at the bit offset.
+    Link(Cid, u32),
+    /// Value of the key, found in a bucket of the node.
+    Found(Ipld),
+    /// Key does not exist within the KAMT.
+    Absent,
+}
+
+/// Looks up `key` in the KAMT with root `root`, loading all nodes through the store. Returns the
+/// value if it exists, along with the bytes of the last node searched.
+pub(crate) fn lookup<BS: BlockStore>(
+    bs: &BS,
+    root: &Cid,
+    hash: &[u8],
+    key: &[u8],
+    bit_width: u32,
+) -> Result<(Option<Ipld>, Vec<u8>)> {
+    let mut cid = *root;
+    let mut offset = 0;
+    loop {
+        let bytes = load_bytes(bs, &cid)?;
+        match search_node(&bytes, hash, offset, key, bit_width)? {
+            Lookup::Link(child, next) => {
+                cid = child;
+                offset = next;
+            }
+            Lookup::Found(value) => return Ok((Some(value), bytes)),
+            Lookup::Absent => return Ok((None, bytes)),
+        }
+    }
+}
+
+/// Verifies that the nodes, ordered from the root, are the path to `key`, which is stored in
+/// buckets as `key` and hashed as `hash`, in the KAMT with root `root`. Returns the value of the
+/// key, or `None` if the nodes prove the key does not exist.
+pub(crate) fn verify<'a, I, H>(
+    nodes: I,
+    root: &Cid,
+    hash: &[u8],
+    key: &[u8],
+    bit_width: u32,
+    hasher: &H,
+) -> Result<Option<Ipld>>
+where
+    I: IntoIterator<Item = &'a Vec<u8>>,
+    H: ProofHasher + ?Sized,
+{
+    let mut expected = *root;
+    let mut nodes = nodes.into_iter();
+    let mut offset = 0;
+    loop {
+        let node = nodes.next().ok_or(Error::IncompleteProof)?;
+        check_cid_with(node, &expected, hasher)?;
+        let value = match search_node(node, hash, offset, key, bit_width)? {
+            Lookup::Link(child, next) => {
+                expected = child;
+                offset = next;
+                continue;
+            }
+            Lookup::Found(value) => Some(value),
+            Lookup::Absent => None,
+        };
+        if nodes.next().is_some() {
+            return Err(Error::UnexpectedNodes.into());
+        }
+        return Ok(value);
+    }
+}
+
+/// Reads `n` bits from the key at the bit offset, most significant bit first, with the bits past
+/// the end of the key read as zeros.
+fn key_bits(hash: &[u8], offset: u32, n: u32) -> Result<u32> {
+    if offset as usize >= hash.len() * 8 {
+        return Err(Error::InvalidNode("maximum KAMT depth exceeded".into()).into());
+    }
+    let mut out = 0;
+    for i in offset..offset + n {
+        let bit = hash
+            .get((i / 8) as usize)
+            .map_or(0, |b| (b >> (7 - i % 8)) & 1);
+        out = (out << 1) | bit as u32;
+    }
+    Ok(out)
+}
+
+fn search_node(
+    bytes: &[u8],
+    hash: &[u8],
+    offset: u32,
+    key: &[u8],
+    bit_width: u32,
+) -> Result<Lookup> {
+    let (bitfield, pointers): (BytesDe, Vec<Ipld>) = forest_encoding::from_slice(bytes)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode KAMT node: {}", e)))?;
+    let idx = key_bits(hash, offset, bit_width)?;
+    if !bit_set(&bitfield.0, idx) {
+        return Ok(Lookup::Absent);
+    }
+
+    let position = (0..idx).filter(|i| bit_set(&bitfield.0, *i)).count();
+    let offset = offset + bit_width;
+    match pointers.get(position) {
+        Some(Ipld::Link(cid)) => Ok(Lookup::Link(*cid, offset)),
+        Some(Ipld::List(pointer)) => match pointer.as_slice() {
+            [Ipld::List(ext), Ipld::Link(cid)] => {
+                let (consumed, path) = decode_extension(ext)?;
+                if (offset + consumed) as usize > hash.len() * 8 {
+                    return Err(Error::InvalidNode("KAMT extension exceeds key".into()).into());
+                }
+                for i in 0..consumed {
+                    let path_bit = (path[(i / 8) as usize] >> (7 - i % 8)) & 1;
+                    if key_bits(hash, offset + i, 1)? != path_bit as u32 {
+                        return Ok(Lookup::Absent);
+                    }
+                }
+                Ok(Lookup::Link(*cid, offset + consumed))
+            }
+            bucket => {
+                for entry in bucket {
+                    match entry {
+                        Ipld::List(kv) if kv.len() == 2 => {
+                            if kv[0] == Ipld::Bytes(key.to_vec()) {
+                                return Ok(Lookup::Found(kv[1].clone()));
+                            }
+                        }
+                        _ => {
+                            return Err(
+                                Error::InvalidNode("invalid KAMT bucket entry".into()).into()
+                            )
+                        }
+                    }
+                }
+                Ok(Lookup::Absent)
+            }
+        },
+        _ => Err(Error::InvalidNode("invalid KAMT pointer".into()).into()),
+    }
+}
+
+/// Decodes an extension into the number of bits consumed and the path bits.
+fn decode_extension(ext: &[Ipld]) -> Result<(u32, &[u8])> {
+    match ext {
+        [Ipld::Integer(consumed), Ipld::Bytes(path)]
+            if *consumed >= 0 && (*consumed as usize) <= path.len() * 8 =>
+        {
+            Ok((*consumed as u32, path))
+        }
+        _ => Err(Error::InvalidNode("invalid KAMT extension".into()).into()),
+    }
+}
+
+/// Builds a KAMT from the entries of `(hash, key, value)`, with buckets of a single entry and
+/// extensions wherever all keys below a pointer share the next levels, returning the root Cid.
+#[cfg(test)]
+pub(crate) fn build<BS: BlockStore>(bs: &BS, entries: &[(Vec<u8>, Vec<u8>, Ipld)], bw: u32) -> Cid {
+    use crate::DEFAULT_HASH_CODE;
+
+    fn build_node<BS: BlockStore>(
+        bs: &BS,
+        entries: &[&(Vec<u8>, Vec<u8>, Ipld)],
+        offset: u32,
+        bw: u32,
+    ) -> Cid {
+        let mut groups = std::collections::BTreeMap::<u32, Vec<_>>::new();
+        for entry in entries {
+            let idx = key_bits(&entry.0, offset, bw).unwrap();
+            groups.entry(idx).or_default().push(*entry);
+        }
+
+        let mut bitfield = [0u8; 32];
+        let mut pointers = Vec::new();
+        for (idx, group) in groups {
+            bitfield[31 - (idx / 8) as usize] |= 1 << (idx % 8);
+            if let [(_, k, v)] = group.as_slice() {
+                pointers.push(Ipld::List(vec![Ipld::List(vec![
+                    Ipld::Bytes(k.clone()),
+                    v.clone(),
+                ])]));
+                continue;
+            }
+
+            // Skip the levels where all keys share the same bits.
+            let next = offset + bw;
+            let mut levels = 0;
+            while group.iter().all(|e| {
+                key_bits(&e.0, next + levels * bw, bw).unwrap()
+                    == key_bits(&group[0].0, next + levels * bw, bw).unwrap()
+            }) {
+                levels += 1;
+            }
+            let consumed = levels * bw;
+            let child = build_node(bs, &group, next + consumed, bw);
+            if consumed == 0 {
+                pointers.push(Ipld::Link(child));
+            } else {
+                let mut path = vec![0u8; ((consumed + 7) >> 3) as usize];
+                for i in 0..consumed {
+                    let bit = key_bits(&group[0].0, next + i, 1).unwrap() as u8;
+                    path[(i / 8) as usize] |= bit << (7 - i % 8);
+                }
+                pointers.push(Ipld::List(vec![
+                    Ipld::List(vec![Ipld::Integer(consumed as i128), Ipld::Bytes(path)]),
+                    Ipld::Link(child),
+                ]));
+            }
+        }
+        let start = bitfield.iter().position(|b| *b != 0).unwrap_or(32);
+        let node = Ipld::List(vec![
+            Ipld::Bytes(bitfield[start..].to_vec()),
+            Ipld::List(pointers),
+        ]);
+        bs.put(&node, DEFAULT_HASH_CODE).unwrap()
+    }
+
+    let entries: Vec<_> = entries.iter().collect();
+    build_node(bs, &entries, 0, bw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProofGenerator;
+
+    fn slot(i: u8, prefix: u8) -> Vec<u8> {
+        let mut hash = vec![0u8; 32];
+        hash[0] = prefix;
+        hash[31] = i;
+        hash
+    }
+
+    #[test]
+    fn extensions() {
+        let bs = forest_db::MemoryDB::default();
+        // Keys which share all but their last bits are stored below an extension.
+        let entries: Vec<_> = (0..4)
+            .map(|i| slot(i, 0))
+            .chain(std::iter::once(slot(9, 0xff)))
+            .map(|hash| (hash.clone(), hash, Ipld::Integer(1)))
+            .collect();
+        let root = build(&bs, &entries, EVM_BIT_WIDTH);
+
+        for (hash, key, value) in &entries {
+            let p_gen = ProofGenerator::new(&bs);
+            let (found, leaf) = lookup(&p_gen, &root, hash, key, EVM_BIT_WIDTH).unwrap();
+            assert_eq!(found.as_ref(), Some(value));
+            let proof = p_gen.generate_proof_raw(leaf, Some(&root)).unwrap();
+            let nodes = proof.nodes().iter().rev();
+            assert_eq!(
+                verify(nodes, &root, hash, key, EVM_BIT_WIDTH, p_gen.hasher()).unwrap(),
+                found
+            );
+        }
+
+        // Key which diverges from an extension's path is absent at the node of the extension.
+        let missing = slot(1, 1);
+        let p_gen = ProofGenerator::new(&bs);
+        let (found, leaf) = lookup(&p_gen, &root, &missing, &missing, EVM_BIT_WIDTH).unwrap();
+        assert_eq!(found, None);
+        let proof = p_gen.generate_proof_raw(leaf, Some(&root)).unwrap();
+        assert_eq!(proof.nodes().len(), 1);
+        let nodes = proof.nodes().iter().rev();
+        assert_eq!(
+            verify(
+                nodes,
+                &root,
+                &missing,
+                &missing,
+                EVM_BIT_WIDTH,
+                p_gen.hasher()
+            )
+            .unwrap(),
+            None
+        );
+    }
+}
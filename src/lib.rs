@@ -1,15 +1,74 @@
+//! Generation and validation of proofs of data in an Ipld dag.
+//!
+//! Without the default `std` feature, the crate is `no_std` and only proof validation is
+//! available, through the [LinkScanner] over slices, [validate_nodes] and a `Proof` which can
+//! be decoded from its CBOR list of nodes.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod actors;
+#[cfg(feature = "std")]
+mod address;
+#[cfg(feature = "std")]
+mod amt;
+#[cfg(feature = "std")]
+mod bigint;
+#[cfg(feature = "std")]
+mod bitfield;
+#[cfg(feature = "std")]
+mod car;
+#[cfg(feature = "std")]
+mod chain;
+#[cfg(feature = "std")]
+mod dag_json;
+#[cfg(feature = "std")]
 mod error;
+#[cfg(feature = "std")]
 mod generator;
+#[cfg(feature = "std")]
+mod hamt;
+mod hasher;
+#[cfg(feature = "std")]
+mod kamt;
 mod link_scanner;
+#[cfg(feature = "std")]
 mod proof;
+#[cfg(feature = "std")]
+mod state;
+mod verifier;
 
+#[cfg(feature = "std")]
 use cid::Code;
 
+#[cfg(feature = "std")]
+pub use self::actors::*;
+#[cfg(feature = "std")]
+pub use self::address::*;
+#[cfg(feature = "std")]
+pub use self::amt::AmtProof;
+#[cfg(feature = "std")]
+pub use self::bigint::{StoragePower, TokenAmount};
+#[cfg(feature = "std")]
+pub use self::bitfield::{BitField, BitFieldProof};
+#[cfg(feature = "std")]
+pub use self::chain::*;
+#[cfg(feature = "std")]
 pub use self::error::*;
+#[cfg(feature = "std")]
 pub use self::generator::*;
+pub use self::hasher::*;
+pub use self::link_scanner::LinkScanner;
+#[cfg(feature = "std")]
 pub use self::proof::*;
+#[cfg(feature = "std")]
+pub use self::state::*;
+pub use self::verifier::*;
 
 /// Hashing function assumption for more succinct proofs. If the proof needs to handle more hashing
 /// functions, then it should be built on a feature, because it would require a Cid be included
 /// with every proof node.
+#[cfg(feature = "std")]
 const DEFAULT_HASH_CODE: Code = Code::Blake2b256;
@@ -1,91 +1,75 @@
-use anyhow::{anyhow, Result};
-use byteorder::{BigEndian, ByteOrder, ReadBytesExt};
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
 use cid::Cid;
-use std::{
-    convert::TryFrom,
-    io::{Cursor, Read, Seek},
-};
+use core::convert::TryFrom;
 
 /// Wrapper of bytes that allows links to be scanned for lazily as an iterator.
-#[derive(Debug)]
-pub(crate) struct LinkScanner<R> {
-    reader: R,
+///
+/// Only the headers of the CBOR items are read, so scanning does not require the standard
+/// library. Without it, [LinkScanner::next_link] returns the bytes of each link's Cid.
+#[derive(Debug, Clone)]
+pub struct LinkScanner<'a> {
+    bytes: &'a [u8],
+    pos: usize,
     remaining: usize,
-    scratch: [u8; 70],
+    offset: usize,
 }
 
-impl<R> LinkScanner<R> {
-    pub fn new(reader: R) -> Self {
+impl<'a> LinkScanner<'a> {
+    /// Creates a scanner over the links of the encoded CBOR node, starting at its first item.
+    pub fn new(bytes: &'a [u8]) -> Self {
         Self {
-            reader,
+            bytes,
+            pos: 0,
             remaining: 1,
-            // TODO the 70 value can be tweaked, don't need much more than 32 bytes for def Cid
-            scratch: [0u8; 70],
+            offset: 0,
         }
     }
-}
-
-impl<'a> From<&'a [u8]> for LinkScanner<Cursor<&'a [u8]>> {
-    fn from(bytes: &'a [u8]) -> Self {
-        Self::new(Cursor::new(bytes))
-    }
-}
 
-impl<'a> From<&'a Vec<u8>> for LinkScanner<Cursor<&'a [u8]>> {
-    fn from(bytes: &'a Vec<u8>) -> Self {
-        Self::new(Cursor::new(bytes))
+    /// Returns the byte offset of the tag of the last link returned.
+    pub fn link_offset(&self) -> u64 {
+        self.offset as u64
     }
-}
-
-impl From<Vec<u8>> for LinkScanner<Cursor<Vec<u8>>> {
-    fn from(bytes: Vec<u8>) -> Self {
-        Self::new(Cursor::new(bytes))
-    }
-}
 
-impl<R> Iterator for LinkScanner<R>
-where
-    R: Read + Seek,
-{
-    type Item = Cid;
-
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Returns the bytes of the Cid of the next link, without the multibase prefix. Scanning
+    /// stops at the end of the node or at the first item which is not valid CBOR.
+    pub fn next_link(&mut self) -> Option<&'a [u8]> {
         while self.remaining > 0 {
-            let (maj, extra) = cbor_read_header_buf(&mut self.reader, &mut self.scratch).ok()?;
+            let start = self.pos;
+            let (maj, extra) = cbor_read_header(self.bytes, &mut self.pos)?;
             match maj {
                 // MajUnsignedInt, MajNegativeInt, MajOther
                 0 | 1 | 7 => {}
                 // MajByteString, MajTextString
                 2 | 3 => {
-                    self.reader
-                        .seek(std::io::SeekFrom::Current(extra as i64))
-                        .ok()?;
+                    self.pos = self.pos.checked_add(extra)?;
                 }
                 // MajTag
                 6 => {
                     // Check if the tag refers to a CID
                     if extra == 42 {
-                        let (maj, extra) =
-                            cbor_read_header_buf(&mut self.reader, &mut self.scratch).ok()?;
+                        let (maj, extra) = cbor_read_header(self.bytes, &mut self.pos)?;
                         // The actual CID is expected to be a byte string
-                        if maj != 2 || extra > 100 {
+                        if maj != 2 || extra == 0 {
                             return None;
                         }
-                        self.reader.read_exact(&mut self.scratch[..extra]).ok()?;
-                        let c = Cid::try_from(&self.scratch[1..extra]).ok()?;
+                        let end = self.pos.checked_add(extra)?;
+                        let link = self.bytes.get(self.pos + 1..end)?;
+                        self.pos = end;
                         self.remaining -= 1;
-                        return Some(c);
+                        self.offset = start;
+                        return Some(link);
                     } else {
                         self.remaining += 1;
                     }
                 }
                 // MajArray
                 4 => {
-                    self.remaining += extra;
+                    self.remaining = self.remaining.checked_add(extra)?;
                 }
                 // MajMap
                 5 => {
-                    self.remaining += extra * 2;
+                    self.remaining = self.remaining.checked_add(extra.checked_mul(2)?)?;
                 }
                 _ => {
                     return None;
@@ -97,56 +81,97 @@ where
     }
 }
 
-/// Given a CBOR encoded Buffer, returns a tuple of:
+impl<'a> From<&'a [u8]> for LinkScanner<'a> {
+    fn from(bytes: &'a [u8]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl<'a> From<&'a Vec<u8>> for LinkScanner<'a> {
+    fn from(bytes: &'a Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Iterator for LinkScanner<'_> {
+    type Item = Cid;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Cid::try_from(self.next_link()?).ok()
+    }
+}
+
+/// Returns whether a CBOR item of the encoded bytes starts at the offset, by walking the headers
+/// of the items before it. Strings are skipped over without being read and links are not decoded.
+pub(crate) fn item_starts_at(bytes: &[u8], offset: u64) -> bool {
+    let mut pos = 0usize;
+    let mut remaining = 1usize;
+    while remaining > 0 {
+        if pos as u64 >= offset {
+            return pos as u64 == offset;
+        }
+        let (maj, extra) = match cbor_read_header(bytes, &mut pos) {
+            Some(header) => header,
+            None => return false,
+        };
+        match maj {
+            // MajUnsignedInt, MajNegativeInt, MajOther
+            0 | 1 | 7 => {}
+            // MajByteString, MajTextString
+            2 | 3 => pos = pos.saturating_add(extra),
+            // MajArray
+            4 => remaining = remaining.saturating_add(extra),
+            // MajMap
+            5 => remaining = remaining.saturating_add(extra.saturating_mul(2)),
+            // MajTag
+            6 => remaining += 1,
+            _ => return false,
+        }
+        remaining -= 1;
+    }
+    false
+}
+
+/// Given a CBOR encoded Buffer and the position of an item in it, returns a tuple of:
 /// the type of the CBOR object along with extra
-/// elements we expect to read. More info on this can be found in
-/// Appendix C. of RFC 7049 which defines the CBOR specification.
+/// elements we expect to read, and advances the position past the header. More info on this
+/// can be found in Appendix C. of RFC 7049 which defines the CBOR specification.
 /// This was implemented because the CBOR library we use does not expose low
 /// methods like this, requiring us to deserialize the whole CBOR payload, which
 /// is unnecessary and quite inefficient for our usecase here.
-pub(crate) fn cbor_read_header_buf<B: Read>(br: &mut B, scratch: &mut [u8]) -> Result<(u8, usize)> {
-    let first = br.read_u8()?;
+///
+/// Returns `None` if the header is truncated, or is not canonical.
+pub(crate) fn cbor_read_header(bytes: &[u8], pos: &mut usize) -> Option<(u8, usize)> {
+    let first = *bytes.get(*pos)?;
     let maj = (first & 0xe0) >> 5;
     let low = first & 0x1f;
-
+    let len = match low {
+        0..=23 => 0,
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return None,
+    };
+    let extra = bytes.get(*pos + 1..*pos + 1 + len)?;
+    let mut val = 0u64;
+    for b in extra {
+        val = (val << 8) | *b as u64;
+    }
+    // Values must be encoded with the smallest length which fits them.
+    let min = match low {
+        24 => 24,
+        25 => u8::MAX as u64 + 1,
+        26 => u16::MAX as u64 + 1,
+        27 => u32::MAX as u64 + 1,
+        _ => 0,
+    };
     if low < 24 {
-        Ok((maj, low as usize))
-    } else if low == 24 {
-        let val = br.read_u8()?;
-        if val < 24 {
-            return Err(anyhow!(
-                "cbor input was not canonical (lval 24 with value < 24)"
-            ));
-        }
-        Ok((maj, val as usize))
-    } else if low == 25 {
-        br.read_exact(&mut scratch[..2])?;
-        let val = BigEndian::read_u16(&scratch[..2]);
-        if val <= u8::MAX as u16 {
-            return Err(anyhow!(
-                "cbor input was not canonical (lval 25 with value <= MaxUint8)"
-            ));
-        }
-        Ok((maj, val as usize))
-    } else if low == 26 {
-        br.read_exact(&mut scratch[..4])?;
-        let val = BigEndian::read_u32(&scratch[..4]);
-        if val <= u16::MAX as u32 {
-            return Err(anyhow!(
-                "cbor input was not canonical (lval 26 with value <= MaxUint16)"
-            ));
-        }
-        Ok((maj, val as usize))
-    } else if low == 27 {
-        br.read_exact(&mut scratch[..8])?;
-        let val = BigEndian::read_u64(&scratch[..8]);
-        if val <= u32::MAX as u64 {
-            return Err(anyhow!(
-                "cbor input was not canonical (lval 27 with value <= MaxUint32)"
-            ));
-        }
-        Ok((maj, val as usize))
-    } else {
-        Err(anyhow!("invalid header cbor_read_header_buf"))
+        val = low as u64;
+    } else if val < min {
+        return None;
     }
+    *pos += 1 + len;
+    Some((maj, usize::try_from(val).ok()?))
 }
@@ -1,15 +1,22 @@
-use crate::{link_scanner::LinkScanner, Error, DEFAULT_HASH_CODE};
+use crate::{
+    hasher::hash_cid, link_scanner::LinkScanner, validate_hinted_nodes_with, Blake2bHasher, Error,
+    ProofHasher, ValidationError, DEFAULT_HASH_CODE,
+};
 use anyhow::Result;
-use cid::Cid;
+use cid::{Cid, DAG_CBOR};
 use forest_encoding::ser::SerializeSeq;
-use forest_encoding::serde_bytes;
+use forest_encoding::serde_bytes::{self, ByteBuf};
+use forest_encoding::tuple::*;
 use serde::{
-    de::{Deserializer, SeqAccess, Visitor},
+    de::{self, value::MapAccessDeserializer, Deserializer, MapAccess, SeqAccess, Visitor},
     ser::Serializer,
     Deserialize, Serialize,
 };
 use std::fmt;
 
+/// Version of the [ProofEnvelope] format written by this library.
+pub const ENVELOPE_VERSION: u64 = 1;
+
 /// Describes an Ipld proof.
 /// Contains only nodes connected to the root. These nodes are ordered from the root to the base.
 ///
@@ -26,12 +33,14 @@ impl Serialize for Proof {
     {
         let mut seq = serializer.serialize_seq(Some(self.nodes.len()))?;
         for e in &self.nodes {
-            seq.serialize_element(&serde_bytes::Bytes::new(&e))?;
+            seq.serialize_element(&serde_bytes::Bytes::new(e))?;
         }
         seq.end()
     }
 }
 
+/// Decodes the bare list of nodes, a [CompressedProof] or a [ProofEnvelope], which is checked
+/// against the nodes it describes.
 impl<'de> Deserialize<'de> for Proof {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -40,13 +49,13 @@ impl<'de> Deserialize<'de> for Proof {
         struct ProofVisitor;
 
         impl<'de> Visitor<'de> for ProofVisitor {
-            type Value = Vec<Vec<u8>>;
+            type Value = Proof;
 
             fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
-                formatter.write_str("a vector of bytes")
+                formatter.write_str("a vector of bytes or a proof envelope")
             }
 
-            fn visit_seq<A>(self, mut seq: A) -> Result<Vec<Vec<u8>>, A::Error>
+            fn visit_seq<A>(self, mut seq: A) -> Result<Proof, A::Error>
             where
                 A: SeqAccess<'de>,
             {
@@ -56,27 +65,269 @@ impl<'de> Deserialize<'de> for Proof {
                     Vec::new()
                 };
 
-                while let Some(elem) = seq.next_element::<serde_bytes::ByteBuf>()? {
-                    vec.push(elem.into_vec());
+                while let Some(elem) = seq.next_element::<Element>()? {
+                    match elem {
+                        Element::Node(node) => vec.push(node),
+                        // Compressed proofs are the leaf followed by the list of elided nodes.
+                        Element::Elided(nodes) if vec.len() == 1 => {
+                            if seq.next_element::<de::IgnoredAny>()?.is_some() {
+                                return Err(de::Error::custom(
+                                    "unexpected data after elided nodes",
+                                ));
+                            }
+                            let compressed = CompressedProof {
+                                leaf: vec.remove(0),
+                                nodes,
+                            };
+                            return compressed.decompress().map_err(de::Error::custom);
+                        }
+                        Element::Elided(_) => {
+                            return Err(de::Error::custom("unexpected list of elided nodes"))
+                        }
+                    }
+                }
+                // Proofs always include the leaf, which is also the root for a single node.
+                if vec.is_empty() {
+                    return Err(de::Error::custom("proof has no nodes"));
                 }
-                Ok(vec)
+                Ok(Proof { nodes: vec })
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Proof, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                ProofEnvelope::deserialize(MapAccessDeserializer::new(map))?
+                    .into_proof()
+                    .map_err(de::Error::custom)
             }
         }
-        Ok(Proof {
-            nodes: deserializer.deserialize_seq(ProofVisitor)?,
+        deserializer.deserialize_any(ProofVisitor)
+    }
+}
+
+/// [Proof] along with a hint for each node after the leaf, which is the byte offset of the link
+/// to the previous node. Links are checked at the hinted offsets rather than by decoding every
+/// link of the nodes. The headers of the items before each link are still read, but the contents
+/// of strings in the nodes are not.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct HintedProof {
+    pub(crate) proof: Proof,
+    pub(crate) hints: Vec<u64>,
+}
+
+impl HintedProof {
+    /// Validates that the proof nodes are all directly connected to each other, through the
+    /// links at the hinted offsets. Hints which do not point at a tag 42 encoded [Cid] of the
+    /// previous node are rejected, including those pointing within a string of the node.
+    pub fn validate(&self) -> Result<()> {
+        self.validate_with(&Blake2bHasher)
+    }
+
+    /// Validates the proof through the links at the hinted offsets, computing the [Cid] of each
+    /// node with the hasher. See [validate_hinted_nodes_with] for the cost of checking hints.
+    pub fn validate_with<H: ProofHasher + ?Sized>(&self, hasher: &H) -> Result<()> {
+        let nodes = self.proof.nodes();
+        validate_hinted_nodes_with(nodes, &self.hints, hasher).map_err(|e| match e {
+            ValidationError::Empty => Error::IncompleteProof.into(),
+            ValidationError::LinkNotFound(index) => Error::InvalidProof {
+                link: hash_cid(hasher, &nodes[index - 1]),
+                data: nodes[index].clone(),
+            }
+            .into(),
+            e => e.into(),
         })
     }
+
+    /// Returns reference to the proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+
+    /// Returns the byte offset of the link to the previous node in each node after the leaf.
+    pub fn hints(&self) -> &[u64] {
+        &self.hints
+    }
+
+    /// Returns the proof, dropping the hints.
+    pub fn into_proof(self) -> Proof {
+        self.proof
+    }
+}
+
+/// Element of the list a [Proof] is decoded from, which is a node for both the bare and the
+/// compressed form, or the list of elided nodes following the leaf of a [CompressedProof].
+enum Element {
+    Node(Vec<u8>),
+    Elided(Vec<ElidedNode>),
+}
+
+impl<'de> Deserialize<'de> for Element {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ElementVisitor;
+
+        impl<'de> Visitor<'de> for ElementVisitor {
+            type Value = Element;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("bytes or a list of elided nodes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Element, E> {
+                Ok(Element::Node(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Element, E> {
+                Ok(Element::Node(v))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Element, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut nodes = Vec::new();
+                while let Some(node) = seq.next_element()? {
+                    nodes.push(node);
+                }
+                Ok(Element::Elided(nodes))
+            }
+        }
+        deserializer.deserialize_any(ElementVisitor)
+    }
+}
+
+/// Node with the serialized link to the previous node of the proof removed.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+struct ElidedNode {
+    /// Byte offset of the removed link.
+    offset: u64,
+    #[serde(with = "serde_bytes")]
+    node: Vec<u8>,
+}
+
+/// Compressed [Proof], where the serialized link to the previous node is removed from each node
+/// after the leaf. The links are recomputed from the previous nodes when decompressing, and a
+/// [Proof] decodes directly from the serialized compressed form.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct CompressedProof {
+    #[serde(with = "serde_bytes")]
+    leaf: Vec<u8>,
+    nodes: Vec<ElidedNode>,
+}
+
+impl CompressedProof {
+    /// Restores the proof, by inserting the link to the previous node into each node.
+    pub fn decompress(&self) -> Result<Proof> {
+        let mut nodes = Vec::with_capacity(self.nodes.len() + 1);
+        nodes.push(self.leaf.clone());
+        for elided in &self.nodes {
+            let prev = nodes.last().expect("leaf is always included");
+            let link = forest_encoding::to_vec(&cid::new_from_cbor(prev, DEFAULT_HASH_CODE))?;
+            let offset = elided.offset as usize;
+            if offset > elided.node.len() {
+                return Err(Error::InvalidNode("elided link offset out of bounds".into()).into());
+            }
+            let mut node = Vec::with_capacity(elided.node.len() + link.len());
+            node.extend_from_slice(&elided.node[..offset]);
+            node.extend_from_slice(&link);
+            node.extend_from_slice(&elided.node[offset..]);
+            nodes.push(node);
+        }
+        Ok(Proof { nodes })
+    }
+}
+
+/// Serialized sizes of a proof with and without compression, from [Proof::compression_stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CompressionStats {
+    /// Number of nodes in the proof.
+    pub nodes: usize,
+    /// Size of the serialized proof, in bytes.
+    pub original_size: usize,
+    /// Size of the serialized [CompressedProof], in bytes.
+    pub compressed_size: usize,
+}
+
+impl CompressionStats {
+    /// Returns the number of bytes saved by compressing.
+    pub fn saved(&self) -> usize {
+        self.original_size.saturating_sub(self.compressed_size)
+    }
+
+    /// Returns the compressed size as a fraction of the original size.
+    pub fn ratio(&self) -> f64 {
+        self.compressed_size as f64 / self.original_size as f64
+    }
+}
+
+/// Self-describing encoding of a [Proof], which states the format version, the multihash code
+/// and codecs the node Cids are computed with, and the Cids of the root and leaf.
+///
+/// Decoding ignores unknown fields, so fields can be added in later versions without breaking
+/// older readers. [Proof] decodes from both this envelope and the legacy bare list of nodes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProofEnvelope {
+    /// Version of the envelope format.
+    pub version: u64,
+    /// Multihash code of the node Cids.
+    pub hash_code: u64,
+    /// Codec of each node, in the order of `nodes`.
+    pub codecs: Vec<u64>,
+    /// Cid of the root node.
+    pub root: Cid,
+    /// Cid of the leaf node.
+    pub leaf_cid: Cid,
+    /// Serialized nodes, ordered from the leaf to the root.
+    pub nodes: Vec<ByteBuf>,
+}
+
+impl ProofEnvelope {
+    /// Checks the envelope against the nodes it contains and returns the proof. Envelopes with
+    /// a hash function or codec the proof is not computed with are rejected.
+    pub fn into_proof(self) -> Result<Proof> {
+        if self.version == 0 {
+            return Err(Error::InvalidEnvelope("unsupported version 0".into()).into());
+        }
+        let hash_code = u64::from(DEFAULT_HASH_CODE);
+        if self.hash_code != hash_code {
+            return Err(Error::InvalidEnvelope(format!(
+                "unsupported hash code {:#x}, expected {:#x}",
+                self.hash_code, hash_code
+            ))
+            .into());
+        }
+        if self.nodes.is_empty() {
+            return Err(Error::InvalidEnvelope("proof has no nodes".into()).into());
+        }
+        if self.codecs.len() != self.nodes.len() {
+            return Err(Error::InvalidEnvelope("codecs do not match the nodes".into()).into());
+        }
+        if let Some(codec) = self.codecs.iter().find(|c| **c != DAG_CBOR) {
+            return Err(Error::InvalidEnvelope(format!("unsupported codec {:#x}", codec)).into());
+        }
+
+        let proof = Proof {
+            nodes: self.nodes.into_iter().map(ByteBuf::into_vec).collect(),
+        };
+        check_cid(&proof.nodes[0], &self.leaf_cid)?;
+        check_cid(proof.nodes.last().expect("checked not empty"), &self.root)?;
+        Ok(proof)
+    }
 }
 
 impl Proof {
     /// Validates that the proof nodes are all directly connected to each other.
     pub fn validate(&self) -> Result<()> {
-        let mut prev_cid = cid::new_from_cbor(
-            self.nodes
-                .first()
-                .expect("empty proof should be impossible to create"),
-            DEFAULT_HASH_CODE,
-        );
+        self.validate_with(&Blake2bHasher)
+    }
+
+    /// Validates that the proof nodes are all directly connected to each other, computing the
+    /// [Cid] of each node with the hasher.
+    pub fn validate_with<H: ProofHasher + ?Sized>(&self, hasher: &H) -> Result<()> {
+        let mut prev_cid = hash_cid(hasher, self.nodes.first().ok_or(Error::IncompleteProof)?);
 
         for node in self.nodes.iter().skip(1) {
             // Check to make sure the link exists within the parent node.
@@ -88,7 +339,7 @@ impl Proof {
                 .into());
             }
 
-            prev_cid = cid::new_from_cbor(&node, DEFAULT_HASH_CODE);
+            prev_cid = hash_cid(hasher, node);
         }
 
         Ok(())
@@ -96,17 +347,153 @@ impl Proof {
 
     /// Returns [Cid] root of the proof.
     pub fn root(&self) -> Cid {
+        self.root_with(&Blake2bHasher)
+    }
+
+    /// Returns [Cid] root of the proof, computed with the hasher.
+    pub fn root_with<H: ProofHasher + ?Sized>(&self, hasher: &H) -> Cid {
         let root_node = self
             .nodes
             .last()
             .expect("empty proof should be impossible to create");
-        cid::new_from_cbor(root_node, DEFAULT_HASH_CODE)
+        hash_cid(hasher, root_node)
     }
 
     /// Returns reference to nodes in the proof.
     pub fn nodes(&self) -> &[Vec<u8>] {
         &self.nodes
     }
+
+    /// Compresses the proof by removing the link to the previous node from each node. The links
+    /// are recomputed from the nodes when decompressing.
+    ///
+    /// Links are located by scanning the CBOR items of each node, so bytes of the [Cid] within
+    /// a string of the node are never elided. If a node links to the previous node more than
+    /// once, the first link is elided.
+    ///
+    /// Node bytes can not be removed, as they are needed to compute the [Cid] of each node.
+    pub fn compress(&self) -> Result<CompressedProof> {
+        let leaf = self.nodes.first().ok_or(Error::IncompleteProof)?;
+        let mut elided = Vec::with_capacity(self.nodes.len() - 1);
+        for pair in self.nodes.windows(2) {
+            let cid = cid::new_from_cbor(&pair[0], DEFAULT_HASH_CODE);
+            let cid_bytes = cid.to_bytes();
+            let link = forest_encoding::to_vec(&cid)?;
+            let mut scanner = LinkScanner::from(&pair[1]);
+            let offset = std::iter::from_fn(|| scanner.next_link())
+                .position(|found| found == cid_bytes.as_slice())
+                .map(|_| scanner.link_offset() as usize)
+                .filter(|&start| pair[1].get(start..start + link.len()) == Some(link.as_slice()))
+                .ok_or_else(|| Error::InvalidProof {
+                    link: cid,
+                    data: pair[1].clone(),
+                })?;
+            let mut node = pair[1][..offset].to_vec();
+            node.extend_from_slice(&pair[1][offset + link.len()..]);
+            elided.push(ElidedNode {
+                offset: offset as u64,
+                node,
+            });
+        }
+        Ok(CompressedProof {
+            leaf: leaf.clone(),
+            nodes: elided,
+        })
+    }
+
+    /// Returns the serialized sizes of the proof with and without compression.
+    pub fn compression_stats(&self) -> Result<CompressionStats> {
+        Ok(CompressionStats {
+            nodes: self.nodes.len(),
+            original_size: forest_encoding::to_vec(self)?.len(),
+            compressed_size: forest_encoding::to_vec(&self.compress()?)?.len(),
+        })
+    }
+
+    /// Returns the self-describing [ProofEnvelope] of the proof.
+    pub fn to_envelope(&self) -> ProofEnvelope {
+        ProofEnvelope {
+            version: ENVELOPE_VERSION,
+            hash_code: u64::from(DEFAULT_HASH_CODE),
+            codecs: vec![DAG_CBOR; self.nodes.len()],
+            root: self.root(),
+            leaf_cid: cid::new_from_cbor(
+                self.nodes
+                    .first()
+                    .expect("empty proof should be impossible to create"),
+                DEFAULT_HASH_CODE,
+            ),
+            nodes: self.nodes.iter().cloned().map(ByteBuf::from).collect(),
+        }
+    }
+}
+
+/// Checks that the Cid of the node matches the expected Cid.
+pub(crate) fn check_cid(node: &[u8], expected: &Cid) -> Result<()> {
+    check_cid_with(node, expected, &Blake2bHasher)
+}
+
+/// Checks that the Cid of the node, computed with the hasher, matches the expected Cid.
+pub(crate) fn check_cid_with<H: ProofHasher + ?Sized>(
+    node: &[u8],
+    expected: &Cid,
+    hasher: &H,
+) -> Result<()> {
+    let found = hash_cid(hasher, node);
+    if &found != expected {
+        return Err(Error::LinkMismatch {
+            expected: *expected,
+            found,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Asserts that the serialized typed proof fails to deserialize when any of the proofs within it
+/// is replaced by an empty list of nodes.
+#[cfg(test)]
+pub(crate) fn assert_rejects_empty_proofs<T>(proof: &T)
+where
+    T: Serialize + serde::de::DeserializeOwned,
+{
+    use forest_ipld::Ipld;
+
+    /// Collects the path of each list of bytes, which is the serialized form of a [Proof].
+    fn proof_paths(ipld: &Ipld, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+        if let Ipld::List(items) = ipld {
+            if !items.is_empty() && items.iter().all(|i| matches!(i, Ipld::Bytes(_))) {
+                paths.push(path.clone());
+            }
+            for (i, item) in items.iter().enumerate() {
+                path.push(i);
+                proof_paths(item, path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    let ipld: Ipld = forest_encoding::from_slice(&serde_cbor::to_vec(proof).unwrap()).unwrap();
+    let mut paths = Vec::new();
+    proof_paths(&ipld, &mut Vec::new(), &mut paths);
+    assert!(!paths.is_empty(), "no proofs found");
+    for path in paths {
+        let mut emptied = ipld.clone();
+        let mut node = &mut emptied;
+        for i in &path {
+            match node {
+                Ipld::List(items) => node = &mut items[*i],
+                _ => unreachable!(),
+            }
+        }
+        *node = Ipld::List(Vec::new());
+        let bz = forest_encoding::to_vec(&emptied).unwrap();
+        assert!(
+            serde_cbor::from_slice::<T>(&bz).is_err(),
+            "empty proof at {:?} accepted",
+            path
+        );
+    }
 }
 
 #[cfg(test)]
@@ -124,4 +511,160 @@ mod tests {
             proof
         );
     }
+
+    #[test]
+    fn empty_proof() {
+        assert!(serde_cbor::from_slice::<Proof>(&[0x80]).is_err());
+        assert!(Proof { nodes: vec![] }.validate().is_err());
+        // Proofs within other structures are also rejected.
+        assert!(serde_cbor::from_slice::<HintedProof>(&[0x82, 0x80, 0x80]).is_err());
+    }
+
+    #[test]
+    fn compressed() {
+        use crate::{amt, ProofGenerator};
+        use forest_ipld::Ipld;
+
+        let bs = forest_db::MemoryDB::default();
+        let values: Vec<_> = (0..2000).map(Ipld::Integer).collect();
+        let root = amt::build(&bs, &values, Some(3));
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_amt_proof(&root, 1234).unwrap();
+        let proof = proof.proof();
+        assert!(proof.nodes().len() > 2);
+
+        let compressed = proof.compress().unwrap();
+        assert_eq!(&compressed.decompress().unwrap(), proof);
+        let bz = serde_cbor::to_vec(&compressed).unwrap();
+        assert_eq!(
+            serde_cbor::from_slice::<CompressedProof>(&bz).unwrap(),
+            compressed
+        );
+        // Proofs decode transparently from the compressed form.
+        assert_eq!(&serde_cbor::from_slice::<Proof>(&bz).unwrap(), proof);
+
+        let stats = proof.compression_stats().unwrap();
+        assert_eq!(stats.nodes, proof.nodes().len());
+        assert_eq!(stats.compressed_size, bz.len());
+        assert!(stats.saved() >= 38 * (stats.nodes - 1));
+        assert!(stats.ratio() < 1.0);
+
+        // Only links are elided, not the bytes of the Cid within a string of the node.
+        let leaf = proof.nodes()[0].clone();
+        let link = forest_encoding::to_vec(&cid::new_from_cbor(&leaf, DEFAULT_HASH_CODE)).unwrap();
+        let embedded = Ipld::Bytes(link.clone());
+        let linked = Proof {
+            nodes: vec![
+                leaf.clone(),
+                forest_encoding::to_vec(&Ipld::List(vec![
+                    embedded.clone(),
+                    Ipld::Link(cid::new_from_cbor(&leaf, DEFAULT_HASH_CODE)),
+                ]))
+                .unwrap(),
+            ],
+        };
+        let compressed = linked.compress().unwrap();
+        let offset = 1 + 2 + link.len();
+        assert_eq!(compressed.nodes[0].offset, offset as u64);
+        assert_eq!(compressed.nodes[0].node, linked.nodes[1][..offset]);
+        assert_eq!(compressed.decompress().unwrap(), linked);
+
+        let unlinked = Proof {
+            nodes: vec![
+                leaf,
+                forest_encoding::to_vec(&Ipld::List(vec![embedded])).unwrap(),
+            ],
+        };
+        assert!(unlinked.compress().is_err());
+
+        // Single node proofs have no links to elide.
+        let single = Proof {
+            nodes: vec![proof.nodes()[0].clone()],
+        };
+        let bz = serde_cbor::to_vec(&single.compress().unwrap()).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Proof>(&bz).unwrap(), single);
+    }
+
+    #[test]
+    fn hinted() {
+        use crate::{amt, ProofGenerator};
+        use forest_ipld::Ipld;
+
+        let bs = forest_db::MemoryDB::default();
+        let values: Vec<_> = (0..2000).map(|i| Ipld::Bytes(vec![i as u8; 40])).collect();
+        let root = amt::build(&bs, &values, Some(3));
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_amt_proof(&root, 1234).unwrap();
+        let leaf = proof.proof().nodes()[0].clone();
+        let hinted = p_gen.generate_hinted_proof_raw(leaf, Some(&root)).unwrap();
+        assert_eq!(hinted.proof(), proof.proof());
+        assert_eq!(hinted.hints().len(), hinted.proof().nodes().len() - 1);
+        hinted.validate().unwrap();
+        let bz = serde_cbor::to_vec(&hinted).unwrap();
+        assert_eq!(serde_cbor::from_slice::<HintedProof>(&bz).unwrap(), hinted);
+
+        // Hints must point exactly at the link, and one is needed for each parent.
+        let mut shifted = serde_cbor::from_slice::<HintedProof>(&bz).unwrap();
+        shifted.hints[0] += 1;
+        assert!(shifted.validate().is_err());
+        shifted.hints[0] = u64::MAX;
+        assert!(shifted.validate().is_err());
+        shifted.hints.pop();
+        assert!(shifted.validate().is_err());
+
+        // Link bytes within a byte string match at the offset, but are not a tag 42 Cid.
+        let child = cid::new_from_cbor(&hinted.proof.nodes[0], DEFAULT_HASH_CODE);
+        let link = forest_encoding::to_vec(&child).unwrap();
+        let parent = forest_encoding::to_vec(&Ipld::Bytes(link)).unwrap();
+        let forged = HintedProof {
+            proof: Proof {
+                nodes: vec![hinted.proof.nodes[0].clone(), parent],
+            },
+            hints: vec![2],
+        };
+        assert!(forged.validate().is_err());
+    }
+
+    #[test]
+    fn envelope() {
+        use forest_ipld::{to_ipld, Ipld};
+
+        let proof = Proof {
+            nodes: vec![b"leaf".to_vec(), b"root".to_vec()],
+        };
+        let envelope = proof.to_envelope();
+        assert_eq!(envelope.version, ENVELOPE_VERSION);
+        assert_eq!(envelope.root, proof.root());
+        let bz = serde_cbor::to_vec(&envelope).unwrap();
+        assert_eq!(
+            serde_cbor::from_slice::<ProofEnvelope>(&bz).unwrap(),
+            envelope
+        );
+        assert_eq!(serde_cbor::from_slice::<Proof>(&bz).unwrap(), proof);
+
+        // Fields added by later versions are ignored.
+        let mut fields = match to_ipld(&envelope).unwrap() {
+            Ipld::Map(fields) => fields,
+            _ => unreachable!(),
+        };
+        fields.insert("version".into(), Ipld::Integer(2));
+        fields.insert("extension".into(), Ipld::String("new".into()));
+        let bz = serde_cbor::to_vec(&Ipld::Map(fields)).unwrap();
+        assert_eq!(serde_cbor::from_slice::<Proof>(&bz).unwrap(), proof);
+
+        // Envelopes which do not describe their nodes are rejected.
+        let mut wrong_root = envelope.clone();
+        wrong_root.root = wrong_root.leaf_cid;
+        let mut wrong_hash = envelope.clone();
+        wrong_hash.hash_code = 0x12;
+        let mut wrong_codec = envelope.clone();
+        wrong_codec.codecs[1] = cid::RAW;
+        let mut missing_codec = envelope;
+        missing_codec.codecs.pop();
+        for envelope in [wrong_root, wrong_hash, wrong_codec, missing_codec].iter() {
+            let bz = serde_cbor::to_vec(envelope).unwrap();
+            assert!(serde_cbor::from_slice::<Proof>(&bz).is_err());
+            assert!(envelope.clone().into_proof().is_err());
+        }
+    }
 }
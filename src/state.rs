@@ -0,0 +1,386 @@
+use crate::{
+    bigint, check_cid_with, hamt, load_bytes, Address, Blake2bHasher, Error, Proof, ProofGenerator,
+    ProofHasher, TokenAmount,
+};
+use anyhow::{anyhow, Result};
+use cid::Cid;
+use forest_encoding::tuple::*;
+use ipld_blockstore::BlockStore;
+use serde::Serialize;
+
+/// Root of the state tree, which links to the HAMT of actors keyed by address.
+/// Only state tree versions 1 and above are supported.
+#[derive(Serialize_tuple, Deserialize_tuple)]
+pub(crate) struct StateRoot {
+    pub version: u64,
+    pub actors: Cid,
+    pub info: Cid,
+}
+
+/// State of an actor within the state tree.
+#[derive(Debug, Clone, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ActorState {
+    /// Code Cid of the actor, which identifies the actor type.
+    pub code: Cid,
+    /// Cid of the root of the actor's state.
+    pub head: Cid,
+    /// Sequence number of the actor, also known as the nonce.
+    pub sequence: u64,
+    /// Balance of the actor.
+    #[serde(with = "bigint")]
+    pub balance: TokenAmount,
+    /// Delegated address of the actor, only included in newer state trees.
+    #[serde(default)]
+    pub delegated_address: Option<Address>,
+}
+
+/// Proof which is verified against a state root. This allows proofs of the state tree to be
+/// chained from other structures which link to a state root, such as a block header.
+pub trait StateProof {
+    /// Data returned when the proof is verified.
+    type Output;
+
+    /// Verifies the proof against the state root.
+    fn verify_state(&self, state_root: &Cid) -> Result<Self::Output> {
+        self.verify_state_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the
+    /// hasher.
+    fn verify_state_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<Self::Output>;
+}
+
+impl StateProof for Proof {
+    type Output = ();
+
+    fn verify_state_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<()> {
+        let root = self.root_with(hasher);
+        if &root != state_root {
+            return Err(Error::LinkMismatch {
+                expected: *state_root,
+                found: root,
+            }
+            .into());
+        }
+        self.validate_with(hasher)
+    }
+}
+
+/// Proof of an actor's state within the state tree.
+///
+/// The proof nodes include the state root, the path through the actors HAMT and the HAMT node
+/// which contains the actor's state.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ActorStateProof {
+    address: Address,
+    proof: Proof,
+}
+
+impl ActorStateProof {
+    /// Verifies the proof against the state root and returns the state of the actor.
+    pub fn verify(&self, state_root: &Cid) -> Result<ActorState> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the
+    /// hasher, and returns the state of the actor.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<ActorState> {
+        let mut nodes = self.proof.nodes().iter().rev();
+        let actors = decode_state_root(nodes.next(), state_root, hasher)?;
+        let value = hamt::verify(
+            nodes,
+            &actors,
+            &self.address.to_bytes(),
+            hamt::DEFAULT_BIT_WIDTH,
+            hasher,
+        )?
+        .ok_or(Error::KeyNotFound)?;
+        forest_ipld::from_ipld(&value).map_err(|e| anyhow!(e))
+    }
+
+    /// Returns the address of the actor being proven.
+    pub fn address(&self) -> &Address {
+        &self.address
+    }
+
+    /// Returns reference to the underlying proof.
+    pub fn proof(&self) -> &Proof {
+        &self.proof
+    }
+}
+
+impl StateProof for ActorStateProof {
+    type Output = ActorState;
+
+    fn verify_state_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<ActorState> {
+        self.verify_with(state_root, hasher)
+    }
+}
+
+/// Proof of data within the state of an actor. This chains two segments: the proof of the actor
+/// within the state tree and the proof of the data up to the actor's state head.
+///
+/// Verifying checks the actor against the state root and that the second segment is rooted at
+/// the actor's head, so the proof covers the full path from the state root to the data.
+#[derive(Debug, PartialEq, Serialize_tuple, Deserialize_tuple)]
+pub struct ActorHeadProof {
+    actor: ActorStateProof,
+    head: Proof,
+}
+
+impl ActorHeadProof {
+    /// Verifies the proof against the state root and returns the state of the actor.
+    pub fn verify(&self, state_root: &Cid) -> Result<ActorState> {
+        self.verify_with(state_root, &Blake2bHasher)
+    }
+
+    /// Verifies the proof against the state root, computing the [Cid] of each node with the
+    /// hasher, and returns the state of the actor.
+    pub fn verify_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<ActorState> {
+        let actor = self.actor.verify_with(state_root, hasher)?;
+        self.head.verify_state_with(&actor.head, hasher)?;
+        Ok(actor)
+    }
+
+    /// Returns the [Cid] root of the proof, which is the state root.
+    pub fn root(&self) -> Cid {
+        self.actor.proof.root()
+    }
+
+    /// Returns the serialized bytes of the data being proven.
+    pub fn leaf(&self) -> &[u8] {
+        &self.head.nodes()[0]
+    }
+
+    /// Returns an iterator over all nodes in the proof, ordered from the leaf to the state root.
+    pub fn nodes(&self) -> impl Iterator<Item = &Vec<u8>> {
+        self.head.nodes().iter().chain(self.actor.proof.nodes())
+    }
+
+    /// Returns reference to the segment proving the actor's state.
+    pub fn actor_proof(&self) -> &ActorStateProof {
+        &self.actor
+    }
+
+    /// Returns reference to the segment proving the data from the actor's head.
+    pub fn head_proof(&self) -> &Proof {
+        &self.head
+    }
+}
+
+impl StateProof for ActorHeadProof {
+    type Output = ActorState;
+
+    fn verify_state_with<H: ProofHasher + ?Sized>(
+        &self,
+        state_root: &Cid,
+        hasher: &H,
+    ) -> Result<ActorState> {
+        self.verify_with(state_root, hasher)
+    }
+}
+
+/// Checks the first node against the state root and returns the root of the actors HAMT.
+pub(crate) fn decode_state_root<H: ProofHasher + ?Sized>(
+    node: Option<&Vec<u8>>,
+    state_root: &Cid,
+    hasher: &H,
+) -> Result<Cid> {
+    let node = node.ok_or(Error::IncompleteProof)?;
+    check_cid_with(node, state_root, hasher)?;
+    let root: StateRoot = forest_encoding::from_slice(node)
+        .map_err(|e| Error::InvalidNode(format!("failed to decode state root: {}", e)))?;
+    Ok(root.actors)
+}
+
+impl<BS, H> ProofGenerator<'_, BS, H>
+where
+    BS: BlockStore,
+    H: ProofHasher,
+{
+    /// Loads the state of the actor with the address from the state tree. All nodes loaded are
+    /// tracked, so this can be used before generating a proof of data within the actor's state.
+    pub fn get_actor(&self, state_root: &Cid, addr: &Address) -> Result<Option<ActorState>> {
+        Ok(self.lookup_actor(state_root, addr)?.0)
+    }
+
+    /// Generates a proof of the state of the actor with the address in the state tree.
+    pub fn generate_actor_proof(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+    ) -> Result<ActorStateProof> {
+        let (actor, leaf) = self.lookup_actor(state_root, addr)?;
+        if actor.is_none() {
+            return Err(Error::KeyNotFound.into());
+        }
+        Ok(ActorStateProof {
+            address: addr.clone(),
+            proof: self.generate_proof_raw(leaf, Some(state_root))?,
+        })
+    }
+
+    /// Generates a proof of the item within the state of the actor with the address. The item
+    /// must have been loaded through the proof generator from the actor's state head.
+    pub fn generate_actor_head_proof<I: Serialize>(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+        proof_item: &I,
+    ) -> Result<ActorHeadProof> {
+        let actor = self
+            .get_actor(state_root, addr)?
+            .ok_or(Error::KeyNotFound)?;
+        Ok(ActorHeadProof {
+            actor: self.generate_actor_proof(state_root, addr)?,
+            head: self.generate_proof_to_cid(proof_item, &actor.head)?,
+        })
+    }
+
+    fn lookup_actor(
+        &self,
+        state_root: &Cid,
+        addr: &Address,
+    ) -> Result<(Option<ActorState>, Vec<u8>)> {
+        let root: StateRoot = forest_encoding::from_slice(&load_bytes(self, state_root)?)?;
+        let (value, leaf) = hamt::lookup(
+            self,
+            &root.actors,
+            &addr.to_bytes(),
+            hamt::DEFAULT_BIT_WIDTH,
+        )?;
+        let actor = value
+            .map(|v| forest_ipld::from_ipld(&v).map_err(|e| anyhow!(e)))
+            .transpose()?;
+        Ok((actor, leaf))
+    }
+}
+
+/// Builds a state tree with an actor for each ID, returning the state root.
+#[cfg(test)]
+pub(crate) fn build_state_tree<BS: BlockStore>(bs: &BS, actors: &[(Address, ActorState)]) -> Cid {
+    let entries: Vec<_> = actors
+        .iter()
+        .map(|(addr, actor)| (addr.to_bytes(), forest_ipld::to_ipld(actor).unwrap()))
+        .collect();
+    let actors = hamt::build(bs, &entries, hamt::DEFAULT_BIT_WIDTH);
+    let info = bs.put(&(0u8,), crate::DEFAULT_HASH_CODE).unwrap();
+    bs.put(
+        &StateRoot {
+            version: 4,
+            actors,
+            info,
+        },
+        crate::DEFAULT_HASH_CODE,
+    )
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DEFAULT_HASH_CODE;
+    use forest_encoding::BytesDe;
+
+    fn actor_state(id: u64, head: Cid) -> ActorState {
+        ActorState {
+            code: head,
+            head,
+            sequence: id,
+            balance: id as i128 * 1_000,
+            delegated_address: None,
+        }
+    }
+
+    #[test]
+    fn actor_head_proof() {
+        let bs = forest_db::MemoryDB::default();
+        let field = bs.put(&"miner info", DEFAULT_HASH_CODE).unwrap();
+        let head = bs.put(&(field, 8u8), DEFAULT_HASH_CODE).unwrap();
+        let other_head = bs.put(&"other", DEFAULT_HASH_CODE).unwrap();
+        let actors: Vec<_> = (0..100)
+            .map(|id| (Address::new_id(id), actor_state(id, other_head)))
+            .chain(std::iter::once((
+                Address::new_id(1000),
+                actor_state(1000, head),
+            )))
+            .collect();
+        let state_root = build_state_tree(&bs, &actors);
+        let addr = Address::new_id(1000);
+
+        let p_gen = ProofGenerator::new(&bs);
+        let actor = p_gen.get_actor(&state_root, &addr).unwrap().unwrap();
+        assert_eq!(actor, actor_state(1000, head));
+        let (field, _): (Cid, u8) = p_gen.get(&actor.head).unwrap().unwrap();
+        let info: String = p_gen.get(&field).unwrap().unwrap();
+
+        let proof = p_gen
+            .generate_actor_head_proof(&state_root, &addr, &info)
+            .unwrap();
+        assert_eq!(proof.verify(&state_root).unwrap(), actor);
+        assert_eq!(proof.root(), state_root);
+        assert_eq!(
+            forest_encoding::from_slice::<String>(proof.leaf()).unwrap(),
+            info
+        );
+        assert!(proof.verify(&other_head).is_err());
+
+        // Serialized proof is symmetric
+        let bz = serde_cbor::to_vec(&proof).unwrap();
+        assert_eq!(
+            serde_cbor::from_slice::<ActorHeadProof>(&bz).unwrap(),
+            proof
+        );
+        crate::assert_rejects_empty_proofs(&proof);
+
+        // Segments which are not connected through the actor's head are invalid.
+        let mismatched = ActorHeadProof {
+            actor: p_gen
+                .generate_actor_proof(&state_root, &Address::new_id(3))
+                .unwrap(),
+            head: proof.head,
+        };
+        assert!(mismatched.verify(&state_root).is_err());
+    }
+
+    #[test]
+    fn actor_state_without_delegated_address() {
+        let head = cid::new_from_cbor(&[], DEFAULT_HASH_CODE);
+        let bz = forest_encoding::to_vec(&(head, head, 3u64, BytesDe(vec![0, 1]))).unwrap();
+        let actor: ActorState = forest_encoding::from_slice(&bz).unwrap();
+        assert_eq!(actor.balance, 1);
+        assert_eq!(actor.delegated_address, None);
+    }
+
+    #[test]
+    fn missing_actor() {
+        let bs = forest_db::MemoryDB::default();
+        let head = bs.put(&"head", DEFAULT_HASH_CODE).unwrap();
+        let state_root = build_state_tree(&bs, &[(Address::new_id(1), actor_state(1, head))]);
+
+        let p_gen = ProofGenerator::new(&bs);
+        let addr = Address::new_id(2);
+        assert_eq!(p_gen.get_actor(&state_root, &addr).unwrap(), None);
+        assert!(p_gen.generate_actor_proof(&state_root, &addr).is_err());
+    }
+}
@@ -0,0 +1,380 @@
+//! Validation of proofs which only depends on `core` and `alloc`, so proofs can be checked
+//! without the standard library, such as within FVM actors or on hardware wallets.
+//!
+//! Nodes are hashed as DAG-CBOR with Blake2b-256 by a [ProofHasher], the same as the Cids
+//! computed when generating proofs, and links are found with the [LinkScanner] without decoding
+//! the nodes.
+
+use crate::hasher::{Blake2bHasher, ProofHasher, NODE_CID_LEN};
+use crate::link_scanner::{cbor_read_header, item_starts_at, LinkScanner};
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt;
+
+/// Length of a serialized link to a proof node, which is the tag, the byte string header, the
+/// multibase prefix and the Cid.
+const LINK_LEN: usize = 5 + NODE_CID_LEN;
+
+/// Error from validating proof nodes with [validate_nodes].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Proof has no nodes.
+    Empty,
+    /// Node at the index does not link to the node before it.
+    LinkNotFound(usize),
+    /// Proof is not a CBOR list of byte strings.
+    InvalidEncoding,
+    /// Proof does not have a link hint for each node after the leaf.
+    InvalidHints,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("proof has no nodes"),
+            Self::LinkNotFound(index) => {
+                write!(f, "node {} does not link to the node before it", index)
+            }
+            Self::InvalidEncoding => f.write_str("proof is not a CBOR list of nodes"),
+            Self::InvalidHints => f.write_str("proof does not have a hint for each parent node"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// Validates that each node, ordered from the leaf to the root, links to the node before it.
+pub fn validate_nodes<N: AsRef<[u8]>>(nodes: &[N]) -> Result<(), ValidationError> {
+    validate_nodes_with(nodes, &Blake2bHasher)
+}
+
+/// Validates that each node links to the node before it, computing the Cids of the nodes with
+/// the hasher.
+pub fn validate_nodes_with<N, H>(nodes: &[N], hasher: &H) -> Result<(), ValidationError>
+where
+    N: AsRef<[u8]>,
+    H: ProofHasher + ?Sized,
+{
+    let leaf = nodes.first().ok_or(ValidationError::Empty)?;
+    let mut prev_cid = hasher.node_cid(leaf.as_ref());
+    for (index, node) in nodes.iter().enumerate().skip(1) {
+        let mut scanner = LinkScanner::new(node.as_ref());
+        let linked = core::iter::from_fn(|| scanner.next_link()).any(|link| link == prev_cid);
+        if !linked {
+            return Err(ValidationError::LinkNotFound(index));
+        }
+        prev_cid = hasher.node_cid(node.as_ref());
+    }
+    Ok(())
+}
+
+/// Validates that each node, ordered from the leaf to the root, links to the node before it at
+/// the hinted byte offset. `hints` has the offset of the link in each node after the leaf.
+pub fn validate_hinted_nodes<N: AsRef<[u8]>>(
+    nodes: &[N],
+    hints: &[u64],
+) -> Result<(), ValidationError> {
+    validate_hinted_nodes_with(nodes, hints, &Blake2bHasher)
+}
+
+/// Validates that each node links to the node before it at the hinted byte offset, computing the
+/// Cids of the nodes with the hasher.
+///
+/// The link at each hint is compared without decoding any link of the node. Checking that the
+/// hint is the start of an item, rather than a position within a string, still walks the
+/// headers of every item before it, so each node costs a read of the headers before its link.
+/// Only the contents of strings are skipped without being read.
+pub fn validate_hinted_nodes_with<N, H>(
+    nodes: &[N],
+    hints: &[u64],
+    hasher: &H,
+) -> Result<(), ValidationError>
+where
+    N: AsRef<[u8]>,
+    H: ProofHasher + ?Sized,
+{
+    let leaf = nodes.first().ok_or(ValidationError::Empty)?;
+    if hints.len() != nodes.len() - 1 {
+        return Err(ValidationError::InvalidHints);
+    }
+
+    let mut link = [0u8; LINK_LEN];
+    link[..5].copy_from_slice(&[0xd8, 0x2a, 0x58, NODE_CID_LEN as u8 + 1, 0x00]);
+    link[5..].copy_from_slice(&hasher.node_cid(leaf.as_ref()));
+    for (index, (node, &offset)) in nodes.iter().skip(1).zip(hints).enumerate() {
+        let node = node.as_ref();
+        let linked = usize::try_from(offset)
+            .ok()
+            .and_then(|start| node.get(start..start.checked_add(LINK_LEN)?))
+            == Some(&link[..]);
+        if !linked || !item_starts_at(node, offset) {
+            return Err(ValidationError::LinkNotFound(index + 1));
+        }
+        link[5..].copy_from_slice(&hasher.node_cid(node));
+    }
+    Ok(())
+}
+
+/// Nodes linking a leaf to a root, for validating proofs without the standard library. Nodes
+/// are ordered from the leaf to the root, and are not decoded beyond finding their links.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawProof {
+    nodes: Vec<Vec<u8>>,
+}
+
+impl RawProof {
+    /// Creates a proof from its nodes, ordered from the leaf to the root.
+    pub fn new(nodes: Vec<Vec<u8>>) -> Result<Self, ValidationError> {
+        if nodes.is_empty() {
+            return Err(ValidationError::Empty);
+        }
+        Ok(Self { nodes })
+    }
+
+    /// Decodes a proof serialized as a CBOR list of its nodes. Compressed proofs and envelopes
+    /// are only decoded with the standard library.
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, ValidationError> {
+        Self::new(decode_nodes(bytes)?)
+    }
+
+    /// Validates that the proof nodes are all directly connected to each other.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        validate_nodes(&self.nodes)
+    }
+
+    /// Validates that the proof nodes are all directly connected to each other, computing the
+    /// Cids of the nodes with the hasher.
+    pub fn validate_with<H: ProofHasher + ?Sized>(
+        &self,
+        hasher: &H,
+    ) -> Result<(), ValidationError> {
+        validate_nodes_with(&self.nodes, hasher)
+    }
+
+    /// Returns the bytes of the Cid of the root of the proof.
+    pub fn root(&self) -> [u8; NODE_CID_LEN] {
+        crate::node_cid(
+            self.nodes
+                .last()
+                .expect("empty proof is rejected on creation"),
+        )
+    }
+
+    /// Returns reference to nodes in the proof.
+    pub fn nodes(&self) -> &[Vec<u8>] {
+        &self.nodes
+    }
+}
+
+/// Decodes the nodes of a proof serialized as a CBOR list of byte strings.
+pub fn decode_nodes(bytes: &[u8]) -> Result<Vec<Vec<u8>>, ValidationError> {
+    let mut pos = 0;
+    let len = match cbor_read_header(bytes, &mut pos) {
+        Some((4, len)) => len,
+        _ => return Err(ValidationError::InvalidEncoding),
+    };
+    // Each node takes at least a byte, which bounds the allocation.
+    let mut nodes = Vec::with_capacity(len.min(bytes.len()));
+    for _ in 0..len {
+        let node_len = match cbor_read_header(bytes, &mut pos) {
+            Some((2, node_len)) => node_len,
+            _ => return Err(ValidationError::InvalidEncoding),
+        };
+        let node = pos
+            .checked_add(node_len)
+            .and_then(|end| bytes.get(pos..end))
+            .ok_or(ValidationError::InvalidEncoding)?;
+        nodes.push(node.to_vec());
+        pos += node_len;
+    }
+    if pos != bytes.len() {
+        return Err(ValidationError::InvalidEncoding);
+    }
+    Ok(nodes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{node_cid, NODE_CID_LEN};
+    use alloc::vec;
+
+    /// Encodes a list holding a link to the child node.
+    fn parent_of(child: &[u8]) -> Vec<u8> {
+        let mut node = vec![0x81, 0xd8, 0x2a, 0x58, NODE_CID_LEN as u8 + 1, 0x00];
+        node.extend_from_slice(&node_cid(child));
+        node
+    }
+
+    /// Encodes the nodes as a CBOR list of byte strings.
+    fn encode(nodes: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0x80 | nodes.len() as u8];
+        for node in nodes {
+            match node.len() {
+                len @ 0..=23 => bytes.push(0x40 | len as u8),
+                len => bytes.extend_from_slice(&[0x58, len as u8]),
+            }
+            bytes.extend_from_slice(node);
+        }
+        bytes
+    }
+
+    fn nodes() -> Vec<Vec<u8>> {
+        let leaf = vec![0x82, 0x01, 0x02];
+        let middle = parent_of(&leaf);
+        let root = parent_of(&middle);
+        vec![leaf, middle, root]
+    }
+
+    #[test]
+    fn validate() {
+        let nodes = nodes();
+        assert_eq!(validate_nodes(&nodes), Ok(()));
+        assert_eq!(validate_nodes(&nodes[..1]), Ok(()));
+
+        let mut tampered = nodes.clone();
+        tampered[0].push(0);
+        assert_eq!(
+            validate_nodes(&tampered),
+            Err(ValidationError::LinkNotFound(1))
+        );
+        assert_eq!(
+            validate_nodes(&[nodes[0].clone(), nodes[2].clone()]),
+            Err(ValidationError::LinkNotFound(1))
+        );
+        assert_eq!(validate_nodes::<Vec<u8>>(&[]), Err(ValidationError::Empty));
+
+        // Cid bytes within a byte string are not a link.
+        let mut embedded = vec![0x58, NODE_CID_LEN as u8];
+        embedded.extend_from_slice(&node_cid(&nodes[0]));
+        assert_eq!(
+            validate_nodes(&[nodes[0].clone(), embedded]),
+            Err(ValidationError::LinkNotFound(1))
+        );
+    }
+
+    #[test]
+    fn validate_hinted() {
+        let nodes = nodes();
+        assert_eq!(validate_hinted_nodes(&nodes, &[1, 1]), Ok(()));
+        assert_eq!(
+            validate_hinted_nodes(&nodes, &[1, 2]),
+            Err(ValidationError::LinkNotFound(2))
+        );
+        assert_eq!(
+            validate_hinted_nodes(&nodes, &[1, u64::MAX]),
+            Err(ValidationError::LinkNotFound(2))
+        );
+        assert_eq!(
+            validate_hinted_nodes(&nodes, &[1]),
+            Err(ValidationError::InvalidHints)
+        );
+        assert_eq!(
+            validate_hinted_nodes::<Vec<u8>>(&[], &[]),
+            Err(ValidationError::Empty)
+        );
+
+        // Link bytes within a byte string match at the offset, but are not a link.
+        let mut embedded = vec![0x58, LINK_LEN as u8];
+        embedded.extend_from_slice(&nodes[1][1..]);
+        assert_eq!(
+            validate_hinted_nodes(&[nodes[0].clone(), embedded], &[2]),
+            Err(ValidationError::LinkNotFound(1))
+        );
+    }
+
+    #[test]
+    fn validate_with_hasher() {
+        struct ZeroHasher;
+        impl ProofHasher for ZeroHasher {
+            fn blake2b_256(&self, _: &[u8]) -> [u8; 32] {
+                [0; 32]
+            }
+        }
+        let nodes = nodes();
+        assert_eq!(
+            validate_nodes_with(&nodes, &ZeroHasher),
+            Err(ValidationError::LinkNotFound(1))
+        );
+
+        let mut zero_link = vec![0x81, 0xd8, 0x2a, 0x58, NODE_CID_LEN as u8 + 1, 0x00];
+        zero_link.extend_from_slice(&ZeroHasher.node_cid(&nodes[0]));
+        let zero_nodes = [nodes[0].clone(), zero_link];
+        assert_eq!(validate_nodes_with(&zero_nodes, &ZeroHasher), Ok(()));
+        assert_eq!(
+            validate_nodes(&zero_nodes),
+            Err(ValidationError::LinkNotFound(1))
+        );
+    }
+
+    #[test]
+    fn decode() {
+        let nodes = nodes();
+        assert_eq!(decode_nodes(&encode(&nodes)), Ok(nodes));
+        assert_eq!(decode_nodes(&[0x80]), Ok(Vec::new()));
+        assert_eq!(
+            decode_nodes(&[0x81, 0x42, 0]),
+            Err(ValidationError::InvalidEncoding)
+        );
+        assert_eq!(
+            decode_nodes(&[0x81, 0x41, 0, 0]),
+            Err(ValidationError::InvalidEncoding)
+        );
+        assert_eq!(
+            decode_nodes(&[0x81, 0x01]),
+            Err(ValidationError::InvalidEncoding)
+        );
+        assert_eq!(
+            decode_nodes(&[0x41, 0]),
+            Err(ValidationError::InvalidEncoding)
+        );
+    }
+
+    #[test]
+    fn raw_proof() {
+        let nodes = nodes();
+        let proof = RawProof::from_cbor(&encode(&nodes)).unwrap();
+        assert_eq!(proof.nodes(), nodes.as_slice());
+        assert_eq!(proof.validate(), Ok(()));
+        assert_eq!(proof.root(), node_cid(&nodes[2]));
+        assert_eq!(RawProof::new(nodes.clone()), Ok(proof));
+
+        assert_eq!(RawProof::new(Vec::new()), Err(ValidationError::Empty));
+        assert_eq!(RawProof::from_cbor(&[0x80]), Err(ValidationError::Empty));
+
+        let tampered = RawProof::new(vec![nodes[0].clone(), nodes[2].clone()]).unwrap();
+        assert_eq!(tampered.validate(), Err(ValidationError::LinkNotFound(1)));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn matches_generated_proofs() {
+        use crate::{amt, ProofGenerator, DEFAULT_HASH_CODE};
+        use forest_ipld::Ipld;
+
+        let bs = forest_db::MemoryDB::default();
+        let values: Vec<_> = (0..500).map(Ipld::Integer).collect();
+        let root = amt::build(&bs, &values, Some(3));
+        let p_gen = ProofGenerator::new(&bs);
+        let proof = p_gen.generate_amt_proof(&root, 321).unwrap();
+        let proof = proof.proof();
+
+        for node in proof.nodes() {
+            let cid = cid::new_from_cbor(node, DEFAULT_HASH_CODE);
+            assert_eq!(node_cid(node).as_ref(), cid.to_bytes().as_slice());
+        }
+        let bz = serde_cbor::to_vec(proof).unwrap();
+        let nodes = decode_nodes(&bz).unwrap();
+        assert_eq!(nodes, proof.nodes());
+        assert_eq!(validate_nodes(&nodes), Ok(()));
+        let raw = RawProof::from_cbor(&bz).unwrap();
+        assert_eq!(raw.root().as_ref(), proof.root().to_bytes().as_slice());
+
+        let hinted = p_gen
+            .generate_hinted_proof_raw(nodes[0].clone(), Some(&root))
+            .unwrap();
+        assert_eq!(hinted.proof().nodes(), nodes.as_slice());
+        assert_eq!(validate_hinted_nodes(&nodes, hinted.hints()), Ok(()));
+    }
+}